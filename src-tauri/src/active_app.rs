@@ -0,0 +1,83 @@
+/// Name of the app whose window has focus, e.g. "firefox": the executable name on Windows,
+/// the app name on macOS and the window class on X11. `None` where it can't be told,
+/// such as under most Wayland compositors.
+pub fn frontmost_app() -> Option<String> {
+    #[cfg(target_os = "windows")]
+    {
+        use windows_sys::Win32::Foundation::CloseHandle;
+        use windows_sys::Win32::System::Threading::{
+            OpenProcess, QueryFullProcessImageNameW, PROCESS_NAME_WIN32,
+            PROCESS_QUERY_LIMITED_INFORMATION,
+        };
+        use windows_sys::Win32::UI::WindowsAndMessaging::{
+            GetForegroundWindow, GetWindowThreadProcessId,
+        };
+
+        // SAFETY: the handle is checked before use and closed before returning, and the
+        // buffer outlives the call that fills it
+        let image = unsafe {
+            let window = GetForegroundWindow();
+            if window.is_null() {
+                return None;
+            }
+            let mut pid = 0u32;
+            GetWindowThreadProcessId(window, &mut pid);
+            let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+            if process.is_null() {
+                return None;
+            }
+            let mut buf = [0u16; 1024];
+            let mut len = buf.len() as u32;
+            let ok =
+                QueryFullProcessImageNameW(process, PROCESS_NAME_WIN32, buf.as_mut_ptr(), &mut len);
+            CloseHandle(process);
+            if ok == 0 {
+                return None;
+            }
+            String::from_utf16_lossy(&buf[..len as usize])
+        };
+        std::path::Path::new(&image)
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        // `lsappinfo` answers quickly and, unlike System Events, needs no permission
+        let front = command_output("lsappinfo", &["front"])?;
+        let info = command_output("lsappinfo", &["info", "-only", "name", &front])?;
+        // "LSDisplayName"="Safari"
+        let (_, value) = info.split_once('=')?;
+        Some(value.trim().trim_matches('"').to_string()).filter(|n| !n.is_empty())
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        // _NET_ACTIVE_WINDOW(WINDOW): window id # 0x3a00007
+        let active = command_output("xprop", &["-root", "_NET_ACTIVE_WINDOW"])?;
+        let id = active.split_whitespace().next_back()?;
+        // WM_CLASS(STRING) = "Navigator", "firefox"
+        let class = command_output("xprop", &["-id", id, "WM_CLASS"])?;
+        let (_, values) = class.split_once('=')?;
+        let name = values.rsplit(',').next()?.trim().trim_matches('"');
+        Some(name.to_string()).filter(|n| !n.is_empty())
+    }
+
+    #[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
+    {
+        None
+    }
+}
+
+/// Trimmed stdout of a command that succeeded.
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+pub fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = std::process::Command::new(program)
+        .args(args)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
@@ -0,0 +1,211 @@
+use image::{Rgba, RgbaImage};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Largest padding used, in pixels.
+pub const MAX_PADDING: u32 = 2048;
+/// Largest shadow blur used; blurring gets slower and its buffers larger as it grows.
+pub const MAX_SHADOW_BLUR: f32 = 100.0;
+/// Largest canvas, about 1 GB of pixels, so a stray aspect ratio can't exhaust memory.
+const MAX_CANVAS_PIXELS: u128 = 1 << 28;
+
+/// Canvas fill behind the capture. Colours are "#RRGGBB" or "#RRGGBBAA".
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Background {
+    Solid {
+        color: String,
+    },
+    Gradient {
+        from: String,
+        to: String,
+        /// Degrees, 0 = left to right, 90 = top to bottom.
+        angle: f32,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase", default)]
+pub struct BeautifyOptions {
+    pub background: Background,
+    /// Pixels of background around the capture.
+    #[schemars(range(max = 2048))]
+    pub padding: u32,
+    pub corner_radius: u32,
+    /// Shadow blur radius; 0 disables the shadow.
+    #[schemars(range(min = 0.0, max = 100.0))]
+    pub shadow_blur: f32,
+    /// Vertical shadow offset in pixels.
+    pub shadow_offset: i32,
+    #[schemars(range(min = 0.0, max = 1.0))]
+    pub shadow_opacity: f32,
+    /// Canvas aspect ratio such as "16:9"; the canvas grows to fit and content is never cropped.
+    pub aspect_ratio: Option<String>,
+}
+
+impl Default for BeautifyOptions {
+    fn default() -> Self {
+        Self {
+            background: Background::Gradient {
+                from: "#6A82FB".to_string(),
+                to: "#FC5C7D".to_string(),
+                angle: 45.0,
+            },
+            padding: 64,
+            corner_radius: 12,
+            shadow_blur: 24.0,
+            shadow_offset: 8,
+            shadow_opacity: 0.45,
+            aspect_ratio: None,
+        }
+    }
+}
+
+/// Place `img` on a padded background with rounded corners and a drop shadow. Padding and
+/// shadow blur are capped at `MAX_PADDING` and `MAX_SHADOW_BLUR`.
+pub fn beautify(img: &RgbaImage, opts: &BeautifyOptions) -> Result<RgbaImage, String> {
+    let (w, h) = img.dimensions();
+    // Sizes are worked out in u128 so no padding or aspect ratio can overflow them
+    let padding = u128::from(opts.padding.min(MAX_PADDING));
+    let mut canvas_w = u128::from(w) + padding * 2;
+    let mut canvas_h = u128::from(h) + padding * 2;
+    if let Some(ratio) = &opts.aspect_ratio {
+        let (rw, rh) = parse_aspect_ratio(ratio)?;
+        let (rw, rh) = (u128::from(rw), u128::from(rh));
+        // Grow whichever side is too short so the content always fits
+        if canvas_w * rh > canvas_h * rw {
+            canvas_h = (canvas_w * rh).div_ceil(rw);
+        } else {
+            canvas_w = (canvas_h * rw).div_ceil(rh);
+        }
+    }
+    if canvas_w
+        .checked_mul(canvas_h)
+        .map_or(true, |pixels| pixels > MAX_CANVAS_PIXELS)
+    {
+        return Err(format!(
+            "Beautified image would be too large ({canvas_w}x{canvas_h}); reduce the padding or aspect ratio"
+        ));
+    }
+    // Both fit in u32 now, as neither exceeds MAX_CANVAS_PIXELS
+    let (canvas_w, canvas_h) = (canvas_w as u32, canvas_h as u32);
+
+    let mut canvas = fill_background(canvas_w, canvas_h, &opts.background)?;
+    let x = ((canvas_w - w) / 2) as i64;
+    let y = ((canvas_h - h) / 2) as i64;
+
+    let blur = opts.shadow_blur.min(MAX_SHADOW_BLUR);
+    if blur > 0.0 && opts.shadow_opacity > 0.0 {
+        let margin = (blur * 3.0).ceil() as u32;
+        let alpha = (opts.shadow_opacity.clamp(0.0, 1.0) * 255.0) as u8;
+        let mut shadow = RgbaImage::new(w + margin * 2, h + margin * 2);
+        for (sx, sy, px) in shadow.enumerate_pixels_mut() {
+            if sx >= margin && sy >= margin && sx < margin + w && sy < margin + h {
+                let cov = corner_coverage(sx - margin, sy - margin, w, h, opts.corner_radius);
+                *px = Rgba([0, 0, 0, (alpha as f32 * cov) as u8]);
+            }
+        }
+        let shadow = image::imageops::blur(&shadow, blur / 2.0);
+        image::imageops::overlay(
+            &mut canvas,
+            &shadow,
+            x - margin as i64,
+            y - margin as i64 + opts.shadow_offset as i64,
+        );
+    }
+
+    let mut content = img.clone();
+    if opts.corner_radius > 0 {
+        for (cx, cy, px) in content.enumerate_pixels_mut() {
+            let cov = corner_coverage(cx, cy, w, h, opts.corner_radius);
+            if cov < 1.0 {
+                px.0[3] = (px.0[3] as f32 * cov) as u8;
+            }
+        }
+    }
+    image::imageops::overlay(&mut canvas, &content, x, y);
+
+    Ok(canvas)
+}
+
+/// Parse "#RRGGBB" or "#RRGGBBAA".
+pub fn parse_hex_color(s: &str) -> Result<Rgba<u8>, String> {
+    let hex = s.trim().trim_start_matches('#');
+    let byte = |i: usize| {
+        hex.get(i..i + 2)
+            .and_then(|b| u8::from_str_radix(b, 16).ok())
+            .ok_or_else(|| format!("Invalid color: {s}"))
+    };
+    match hex.len() {
+        6 => Ok(Rgba([byte(0)?, byte(2)?, byte(4)?, 255])),
+        8 => Ok(Rgba([byte(0)?, byte(2)?, byte(4)?, byte(6)?])),
+        _ => Err(format!("Invalid color: {s}")),
+    }
+}
+
+/// Parse "W:H" into a non-zero ratio.
+pub fn parse_aspect_ratio(s: &str) -> Result<(u64, u64), String> {
+    let err = || format!("Invalid aspect ratio: {s} (expected W:H)");
+    let (a, b) = s.split_once(':').ok_or_else(err)?;
+    let a: u64 = a.trim().parse().map_err(|_| err())?;
+    let b: u64 = b.trim().parse().map_err(|_| err())?;
+    if a == 0 || b == 0 {
+        return Err(err());
+    }
+    Ok((a, b))
+}
+
+fn fill_background(w: u32, h: u32, background: &Background) -> Result<RgbaImage, String> {
+    match background {
+        Background::Solid { color } => Ok(RgbaImage::from_pixel(w, h, parse_hex_color(color)?)),
+        Background::Gradient { from, to, angle } => {
+            let from = parse_hex_color(from)?;
+            let to = parse_hex_color(to)?;
+            let (sin, cos) = angle.to_radians().sin_cos();
+            // Project onto the gradient direction, normalised so the corners map to 0 and 1
+            let half = (w as f32 * cos.abs() + h as f32 * sin.abs()) / 2.0;
+            let (cx, cy) = (w as f32 / 2.0, h as f32 / 2.0);
+            Ok(RgbaImage::from_fn(w, h, |x, y| {
+                let d = (x as f32 + 0.5 - cx) * cos + (y as f32 + 0.5 - cy) * sin;
+                let t = if half > 0.0 {
+                    (d / half * 0.5 + 0.5).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                };
+                let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+                Rgba([
+                    mix(from[0], to[0]),
+                    mix(from[1], to[1]),
+                    mix(from[2], to[2]),
+                    mix(from[3], to[3]),
+                ])
+            }))
+        }
+    }
+}
+
+/// Anti-aliased coverage of pixel `(x, y)` inside a `w`×`h` rectangle with rounded corners.
+fn corner_coverage(x: u32, y: u32, w: u32, h: u32, radius: u32) -> f32 {
+    let r = radius.min(w / 2).min(h / 2) as f32;
+    if r <= 0.0 {
+        return 1.0;
+    }
+    let px = x as f32 + 0.5;
+    let py = y as f32 + 0.5;
+    let cx = if px < r {
+        r
+    } else if px > w as f32 - r {
+        w as f32 - r
+    } else {
+        return 1.0;
+    };
+    let cy = if py < r {
+        r
+    } else if py > h as f32 - r {
+        h as f32 - r
+    } else {
+        return 1.0;
+    };
+    let dist = ((px - cx).powi(2) + (py - cy).powi(2)).sqrt();
+    (r - dist + 0.5).clamp(0.0, 1.0)
+}
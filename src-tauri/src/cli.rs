@@ -0,0 +1,347 @@
+use crate::beautify::{self, Background};
+use crate::{capture, config, inspect, retention};
+use std::path::{Path, PathBuf};
+
+const USAGE: &str = "Usage:
+  opencap [--profile <name>]           Open the capture overlay
+  opencap [--portable] ...             Keep config and screenshots next to the executable
+  opencap [--set <key>=<value>]... ... Override a setting for this run (also OPENCAP_<KEY>)
+  opencap --settings                   Open the settings window
+  opencap profiles                     List capture profiles
+  opencap export-settings <file>       Save all settings, including profiles, to a file
+  opencap import-settings <file> [--dry-run]
+                                       Show what a settings file changes, then apply it
+  opencap config-schema                Print the JSON Schema for config.json
+  opencap cleanup [--dry-run] [--profile <name>]
+                                       Move captures over the retention limits to the trash
+  opencap pick-color <x> <y> [--format hex|rgb|hsl|oklch|all]
+  opencap beautify <image>... [--output-dir <dir>] [--padding <px>] [--radius <px>]
+                   [--shadow <blur>] [--background <#hex>] [--gradient <#from>,<#to>[,<angle>]]
+                   [--aspect <w:h>]";
+
+/// Run a headless subcommand if `args` (without the program name) names one.
+/// Returns the process exit code, or `None` to fall through to the GUI.
+pub fn run_cli(args: &[String]) -> Option<i32> {
+    let args = strip_global_flags(args);
+    let (cmd, rest) = args.split_first()?;
+    let command: fn(&[String]) -> Result<(), String> = match cmd.as_str() {
+        "pick-color" => pick_color,
+        "beautify" => beautify_files,
+        "profiles" => |_| list_profiles(),
+        "export-settings" => export_settings,
+        "import-settings" => import_settings,
+        "config-schema" => |_| print_config_schema(),
+        "cleanup" => clean_up,
+        "help" | "--help" | "-h" => |_| {
+            println!("{USAGE}");
+            Ok(())
+        },
+        _ => return None,
+    };
+
+    attach_console();
+    match command(rest) {
+        Ok(()) => Some(0),
+        Err(e) => {
+            eprintln!("opencap: {e}");
+            Some(1)
+        }
+    }
+}
+
+/// Release builds on Windows have no console of their own, so output from a subcommand
+/// would be lost. Write to the console of the shell that started us instead, if any.
+#[cfg(target_os = "windows")]
+fn attach_console() {
+    use windows_sys::Win32::System::Console::{AttachConsole, ATTACH_PARENT_PROCESS};
+    // Fails harmlessly when started without a console or when one is already attached
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+fn attach_console() {}
+
+fn list_profiles() -> Result<(), String> {
+    let cfg = config::load_config();
+    for profile in &cfg.profiles {
+        let marker = if cfg.default_profile.as_deref() == Some(profile.name.as_str()) {
+            " (default)"
+        } else {
+            ""
+        };
+        println!("{}{marker}", profile.name);
+    }
+    Ok(())
+}
+
+fn print_config_schema() -> Result<(), String> {
+    let schema = serde_json::to_string_pretty(&config::config_schema())
+        .map_err(|e| format!("Failed to serialize schema: {e}"))?;
+    println!("{schema}");
+    Ok(())
+}
+
+/// Apply the retention policy to the folders of the given or default profile, or with
+/// `--dry-run` only list what it would remove.
+fn clean_up(args: &[String]) -> Result<(), String> {
+    let dry_run = args.iter().any(|a| a == "--dry-run");
+    let cfg = config::load_config();
+    let requested = flag_value(args, "--profile");
+    if let Some(name) = requested.filter(|name| cfg.find_profile(name).is_none()) {
+        return Err(format!("Unknown profile \"{name}\""));
+    }
+    let cfg = cfg.with_profile(&config::ProfileChoice::requested(requested));
+    if !cfg.retention.is_enabled() {
+        println!("No retention limits are set");
+        return Ok(());
+    }
+
+    let expired = if dry_run {
+        retention::expired_captures(&cfg, None)?
+    } else {
+        retention::clean_up(&cfg, None)?
+    };
+    for e in &expired {
+        println!("{}  ({})", e.capture.path.display(), e.reason);
+    }
+    let bytes: u64 = expired.iter().map(|e| e.capture.size).sum();
+    let mb = bytes as f64 / (1024.0 * 1024.0);
+    match (dry_run, expired.len()) {
+        (_, 0) => println!("Nothing to clean up"),
+        (true, n) => println!("Would move {n} capture(s), {mb:.1} MB, to the trash"),
+        (false, n) => println!("Moved {n} capture(s), {mb:.1} MB, to the trash"),
+    }
+    Ok(())
+}
+
+fn export_settings(args: &[String]) -> Result<(), String> {
+    let [file] = positional_args(args)[..] else {
+        return Err(format!("export-settings expects <file>\n{USAGE}"));
+    };
+    config::export_bundle(Path::new(file))?;
+    println!("{file}");
+    Ok(())
+}
+
+fn import_settings(args: &[String]) -> Result<(), String> {
+    let dry_run = args.iter().any(|a| a == "--dry-run");
+    let args: Vec<String> = args.iter().filter(|a| *a != "--dry-run").cloned().collect();
+    let [file] = positional_args(&args)[..] else {
+        return Err(format!("import-settings expects <file>\n{USAGE}"));
+    };
+    let path = Path::new(file);
+
+    let (imported, warnings) = config::read_bundle(path)?;
+    for warning in &warnings {
+        eprintln!("opencap: {warning}");
+    }
+    let changes = config::diff_configs(&config::load_config(), &imported);
+    if changes.is_empty() {
+        println!("No changes");
+        return Ok(());
+    }
+    let show = |v: &Option<serde_json::Value>| {
+        v.as_ref()
+            .map_or("(unset)".to_string(), |v| v.to_string())
+    };
+    for change in &changes {
+        println!(
+            "{}: {} -> {}",
+            change.field,
+            show(&change.old),
+            show(&change.new)
+        );
+    }
+    if dry_run {
+        return Ok(());
+    }
+
+    config::import_bundle(path).map_err(|errors| {
+        errors
+            .iter()
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>()
+            .join("\n")
+    })?;
+    println!("Imported {} change(s)", changes.len());
+    Ok(())
+}
+
+fn pick_color(args: &[String]) -> Result<(), String> {
+    let positional = positional_args(args);
+    let [x, y] = positional.as_slice() else {
+        return Err(format!("pick-color expects <x> <y>\n{USAGE}"));
+    };
+    let x: i32 = x
+        .parse()
+        .map_err(|_| format!("Invalid x coordinate: {x}"))?;
+    let y: i32 = y
+        .parse()
+        .map_err(|_| format!("Invalid y coordinate: {y}"))?;
+    let format = flag_value(args, "--format").unwrap_or("hex");
+
+    let composite = capture::capture_all_screens()?;
+    let color = inspect::sample_at(
+        &composite.image,
+        (x - composite.origin_x) as i64,
+        (y - composite.origin_y) as i64,
+        x,
+        y,
+    )?;
+
+    if format == "all" {
+        for name in ["hex", "rgb", "hsl", "oklch"] {
+            println!("{name}: {}", color.format(name).unwrap_or_default());
+        }
+    } else {
+        let value = color
+            .format(format)
+            .ok_or_else(|| format!("Unknown format: {format}"))?;
+        println!("{value}");
+    }
+    Ok(())
+}
+
+/// Apply the beautify step to existing images, using the configured style unless overridden.
+fn beautify_files(args: &[String]) -> Result<(), String> {
+    let inputs = positional_args(args);
+    if inputs.is_empty() {
+        return Err(format!("beautify expects at least one image\n{USAGE}"));
+    }
+
+    let mut opts = config::load_config().beautify.options;
+    if let Some(padding) = parse_flag(args, "--padding")? {
+        opts.padding = padding;
+    }
+    if let Some(radius) = parse_flag(args, "--radius")? {
+        opts.corner_radius = radius;
+    }
+    if let Some(blur) = parse_flag(args, "--shadow")? {
+        opts.shadow_blur = blur;
+    }
+    if let Some(color) = flag_value(args, "--background") {
+        opts.background = Background::Solid {
+            color: color.to_string(),
+        };
+    }
+    if let Some(spec) = flag_value(args, "--gradient") {
+        let parts: Vec<&str> = spec.split(',').collect();
+        let (from, to, angle) = match parts.as_slice() {
+            [from, to] => (from, to, 45.0),
+            [from, to, angle] => (
+                from,
+                to,
+                angle
+                    .parse()
+                    .map_err(|_| format!("Invalid gradient angle: {angle}"))?,
+            ),
+            _ => return Err(format!("Invalid gradient: {spec}")),
+        };
+        opts.background = Background::Gradient {
+            from: from.to_string(),
+            to: to.to_string(),
+            angle,
+        };
+    }
+    if let Some(ratio) = flag_value(args, "--aspect") {
+        opts.aspect_ratio = Some(ratio.to_string());
+    }
+    let output_dir = flag_value(args, "--output-dir").map(PathBuf::from);
+
+    let mut failed = false;
+    for input in inputs {
+        let input = Path::new(input);
+        match beautify_file(input, output_dir.as_deref(), &opts) {
+            Ok(out) => println!("{}", out.display()),
+            Err(e) => {
+                eprintln!("opencap: {}: {e}", input.display());
+                failed = true;
+            }
+        }
+    }
+
+    if failed {
+        Err("Some images could not be processed".into())
+    } else {
+        Ok(())
+    }
+}
+
+fn beautify_file(
+    input: &Path,
+    output_dir: Option<&Path>,
+    opts: &beautify::BeautifyOptions,
+) -> Result<PathBuf, String> {
+    let img = image::open(input)
+        .map_err(|e| format!("Failed to open image: {e}"))?
+        .to_rgba8();
+    let out_img = beautify::beautify(&img, opts)?;
+
+    let stem = input
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("image");
+    let dir = output_dir
+        .or_else(|| input.parent())
+        .unwrap_or(Path::new("."));
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create output dir: {e}"))?;
+    let out = dir.join(format!("{stem}_beautified.png"));
+    out_img
+        .save(&out)
+        .map_err(|e| format!("Failed to save image: {e}"))?;
+    Ok(out)
+}
+
+/// Drop switches that apply to every mode and are read elsewhere: `--portable` by
+/// `config::portable_dir` and `--set` by `config::load_config`.
+fn strip_global_flags(args: &[String]) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--portable" => {}
+            "--set" => {
+                iter.next();
+            }
+            _ => out.push(arg.clone()),
+        }
+    }
+    out
+}
+
+/// Parsed value following `flag`, if present.
+fn parse_flag<T: std::str::FromStr>(args: &[String], flag: &str) -> Result<Option<T>, String> {
+    flag_value(args, flag)
+        .map(|v| {
+            v.parse()
+                .map_err(|_| format!("Invalid value for {flag}: {v}"))
+        })
+        .transpose()
+}
+
+/// Value following `flag`, e.g. `--format hex`.
+pub fn flag_value<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|a| a == flag)
+        .and_then(|i| args.get(i + 1))
+        .map(|s| s.as_str())
+}
+
+/// Arguments that are neither flags nor flag values. Negative numbers count as
+/// positional so that coordinates left of the primary monitor still parse.
+fn positional_args(args: &[String]) -> Vec<&str> {
+    let mut out = Vec::new();
+    let mut skip_next = false;
+    for arg in args {
+        if skip_next {
+            skip_next = false;
+        } else if arg.starts_with("--") {
+            skip_next = true;
+        } else {
+            out.push(arg.as_str());
+        }
+    }
+    out
+}
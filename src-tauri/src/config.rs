@@ -1,75 +1,1386 @@
+use crate::beautify::{parse_aspect_ratio, parse_hex_color, Background, BeautifyOptions};
+use crate::storage::{CaptureMode, ImageFormat, DEFAULT_FILENAME_TEMPLATE};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use serde_json::{Map, Value};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+/// Current on-disk format. New fields only need a default; bump this and append to
+/// `MIGRATIONS` when existing values change meaning or move.
+pub const CONFIG_VERSION: u32 = 2;
+
+/// The user's own settings, in the config directory.
+const CONFIG_FILE: &str = "config.json";
+/// Machine-wide defaults, applied underneath the user's settings.
+const SYSTEM_DEFAULTS_FILE: &str = "defaults.json";
+/// Machine-wide policy, applied over the user's settings. Every key it sets is locked.
+const POLICY_FILE: &str = "policy.json";
+
+/// Keys moved by a migration, so overrides written for older versions keep working.
+const RENAMED_KEYS: &[(&str, &str)] = &[("openWithProgram", "openWith.program")];
+/// Keys that describe the file or hold lists of entries rather than settings, so
+/// environment and `--set` overrides can't replace them.
+const NOT_OVERRIDABLE: &[&str] = &["version", "profiles", "folderRules", "customPrograms"];
+
+/// `MIGRATIONS[n]` upgrades a version `n` document to version `n + 1`.
+const MIGRATIONS: &[fn(&mut Map<String, Value>)] = &[migrate_v0_to_v1, migrate_v1_to_v2];
+
+/// OpenCap settings, stored as `config.json` in the config directory.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase", default)]
+#[schemars(title = "OpenCap config")]
 pub struct Config {
+    /// Format version of this file; older files are migrated on load.
+    pub version: u32,
+    /// Copy each capture to the clipboard.
     pub copy_to_clipboard: bool,
+    /// Open each saved capture with `openWith`.
     pub auto_open: bool,
+    /// Save each capture to `savePath`.
     pub save_locally: bool,
+    /// Folder for saved captures; null means Pictures/Screenshots.
     pub save_path: Option<String>,
-    pub open_with_program: String, // "default" or path to program
+    /// Ask for a file name, folder and format for each saved capture, starting in
+    /// `savePath` with the name from `filenameTemplate`.
+    pub save_as_prompt: bool,
+    /// Subfolder of the save folder for each capture, e.g. "{year}/{month}". Takes the
+    /// placeholders of `filenameTemplate` plus {mode}, {monitor} and {app}; empty saves
+    /// directly in the folder.
+    pub subfolder_template: String,
+    /// Rules that send matching captures to other folders; the first match wins.
+    pub folder_rules: Vec<FolderRule>,
+    pub retention: RetentionSettings,
+    pub open_with: OpenWith,
+    /// Programs added by the user to the "Open with" list.
+    pub custom_programs: Vec<CustomProgram>,
+    pub format: ImageFormat,
+    /// JPEG quality from 1 to 100.
+    #[schemars(range(min = 1, max = 100))]
+    pub jpeg_quality: u8,
+    /// File name without extension. Placeholders: {date}, {time}, {year}, {month}, {day},
+    /// {hour}, {minute}, {second}, {profile}, {width}, {height}, {mode}, {monitor}, {app}.
+    pub filename_template: String,
+    pub trim: TrimSettings,
+    pub beautify: BeautifySettings,
+    /// Named sets of overrides, picked with `--profile` or the number keys in the overlay.
+    pub profiles: Vec<Profile>,
+    /// Profile used when none is picked explicitly.
+    pub default_profile: Option<String>,
+    /// Keys this build doesn't know, e.g. from a newer version; kept so saving doesn't drop them
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// The program captures are opened with when `autoOpen` is on.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase", default)]
+pub struct OpenWith {
+    /// "default" for the system handler, a program name or path, or on Linux a `.desktop` file.
+    pub program: String,
+    /// Command-line arguments; empty passes just the file. Placeholders: {path}, {dir},
+    /// {width}, {height}. Quote arguments containing spaces. Not used for `.desktop` files,
+    /// whose Exec line decides the arguments.
+    pub args: String,
+    /// Directory the program starts in; null keeps OpenCap's.
+    pub working_dir: Option<String>,
+}
+
+impl Default for OpenWith {
+    fn default() -> Self {
+        Self {
+            program: "default".to_string(),
+            args: String::new(),
+            working_dir: None,
+        }
+    }
+}
+
+/// A program the user added to the "Open with" list, next to the detected ones.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CustomProgram {
+    pub name: String,
+    /// Program name or path.
+    pub command: String,
+    /// Argument template, as in `openWith.args`.
+    pub args: String,
+    /// Directory the program starts in; null keeps OpenCap's.
+    pub working_dir: Option<String>,
+}
+
+/// Sends captures that meet every condition it sets to `folder`. Unset conditions match
+/// any capture.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase", default)]
+pub struct FolderRule {
+    pub mode: Option<CaptureMode>,
+    /// Profile the capture was taken with.
+    pub profile: Option<String>,
+    /// Monitor the capture is on, counting from 1.
+    #[schemars(range(min = 1))]
+    pub monitor: Option<usize>,
+    /// App that was active when the capture started, e.g. "firefox"; not case-sensitive.
+    pub app: Option<String>,
+    /// Absolute, or relative to `savePath`. `subfolderTemplate` still applies inside it.
+    pub folder: String,
+}
+
+/// Limits on the captures OpenCap saved in the screenshot folders. Captures over any limit
+/// are moved to the trash, oldest first, after each capture. Unset limits don't apply.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RetentionSettings {
+    /// Keep captures from the last this many days.
+    #[schemars(range(min = 1))]
+    pub max_age_days: Option<u32>,
+    /// Keep at most this many megabytes of captures.
+    #[schemars(range(min = 1))]
+    pub max_total_mb: Option<u64>,
+    /// Keep at most this many captures.
+    #[schemars(range(min = 1))]
+    pub max_files: Option<u32>,
+}
+
+impl RetentionSettings {
+    pub fn is_enabled(&self) -> bool {
+        self.max_age_days.is_some() || self.max_total_mb.is_some() || self.max_files.is_some()
+    }
+}
+
+/// Strip uniform margins from captures before they are saved or copied.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TrimSettings {
+    pub enabled: bool,
+    /// Max per-channel difference from the background colour.
+    pub tolerance: u8,
+    /// Pixels of margin kept around the content.
+    pub padding: u32,
+}
+
+impl Default for TrimSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            tolerance: 10,
+            padding: 0,
+        }
+    }
+}
+
+/// Present captures on a styled background with rounded corners and a shadow.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase", default)]
+pub struct BeautifySettings {
+    pub enabled: bool,
+    #[serde(flatten)]
+    pub options: BeautifyOptions,
+}
+
+/// A named set of overrides applied on top of the base settings, e.g. "docs" or "chat".
+/// Fields left unset inherit the base value.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Profile {
+    /// Name used with `--profile`; must be unique.
+    pub name: String,
+    pub copy_to_clipboard: Option<bool>,
+    pub auto_open: Option<bool>,
+    pub save_locally: Option<bool>,
+    pub save_path: Option<String>,
+    pub save_as_prompt: Option<bool>,
+    pub subfolder_template: Option<String>,
+    pub open_with: Option<OpenWith>,
+    pub format: Option<ImageFormat>,
+    #[schemars(range(min = 1, max = 100))]
+    pub jpeg_quality: Option<u8>,
+    pub filename_template: Option<String>,
+    pub trim: Option<TrimSettings>,
+    pub beautify: Option<BeautifySettings>,
+}
+
+/// Which settings a capture uses.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ProfileChoice {
+    /// `defaultProfile` if it names a profile, otherwise the base settings.
+    #[default]
+    Default,
+    /// The base settings, even when `defaultProfile` is set.
+    Base,
+    Named(String),
+}
+
+impl ProfileChoice {
+    /// The profile asked for with `--profile`, or the default one.
+    pub fn requested(name: Option<&str>) -> Self {
+        name.map_or(ProfileChoice::Default, |name| ProfileChoice::Named(name.to_string()))
+    }
+}
+
+impl Config {
+    /// Whether any setting refers to the active app, which is only looked up when needed.
+    pub fn uses_active_app(&self) -> bool {
+        let mut templates = [&self.filename_template, &self.subfolder_template]
+            .into_iter()
+            .chain(self.profiles.iter().flat_map(|p| {
+                p.filename_template
+                    .iter()
+                    .chain(p.subfolder_template.iter())
+            }));
+        self.folder_rules.iter().any(|r| r.app.is_some()) || templates.any(|t| t.contains("{app}"))
+    }
+
+    pub fn find_profile(&self, name: &str) -> Option<&Profile> {
+        self.profiles.iter().find(|p| p.name == name)
+    }
+
+    /// Name of the profile to use, or `None` for the base settings, including when the
+    /// chosen profile doesn't exist.
+    pub fn profile_name(&self, choice: &ProfileChoice) -> Option<String> {
+        let name = match choice {
+            ProfileChoice::Default => self.default_profile.as_deref()?,
+            ProfileChoice::Base => return None,
+            ProfileChoice::Named(name) => name,
+        };
+        self.find_profile(name).map(|p| p.name.clone())
+    }
+
+    /// Effective settings for a capture, with the chosen profile's overrides applied.
+    /// Overrides for this run and the machine policy still win over the profile.
+    pub fn with_profile(&self, choice: &ProfileChoice) -> Config {
+        let mut cfg = self.clone();
+        let Some(profile) = self
+            .profile_name(choice)
+            .and_then(|name| self.find_profile(&name))
+        else {
+            return cfg;
+        };
+
+        if let Some(v) = profile.copy_to_clipboard {
+            cfg.copy_to_clipboard = v;
+        }
+        if let Some(v) = profile.auto_open {
+            cfg.auto_open = v;
+        }
+        if let Some(v) = profile.save_locally {
+            cfg.save_locally = v;
+        }
+        if let Some(v) = &profile.save_path {
+            cfg.save_path = Some(v.clone());
+        }
+        if let Some(v) = profile.save_as_prompt {
+            cfg.save_as_prompt = v;
+        }
+        if let Some(v) = &profile.subfolder_template {
+            cfg.subfolder_template = v.clone();
+        }
+        if let Some(v) = &profile.open_with {
+            cfg.open_with = v.clone();
+        }
+        if let Some(v) = profile.format {
+            cfg.format = v;
+        }
+        if let Some(v) = profile.jpeg_quality {
+            cfg.jpeg_quality = v;
+        }
+        if let Some(v) = &profile.filename_template {
+            cfg.filename_template = v.clone();
+        }
+        if let Some(v) = &profile.trim {
+            cfg.trim = v.clone();
+        }
+        if let Some(v) = &profile.beautify {
+            cfg.beautify = v.clone();
+        }
+
+        let enforced = enforced_layer(&mut Vec::new());
+        if enforced.is_empty() {
+            return cfg;
+        }
+        let mut doc = to_doc(&cfg);
+        merge_into(&mut doc, &enforced);
+        serde_json::from_value(Value::Object(doc)).unwrap_or(cfg)
+    }
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
+            version: CONFIG_VERSION,
             copy_to_clipboard: true,
             auto_open: true,
             save_locally: true,
             save_path: None, // None means use default Pictures/Screenshots
-            open_with_program: "default".to_string(),
+            save_as_prompt: false,
+            subfolder_template: String::new(),
+            folder_rules: Vec::new(),
+            retention: RetentionSettings::default(),
+            open_with: OpenWith::default(),
+            custom_programs: Vec::new(),
+            format: ImageFormat::Png,
+            jpeg_quality: 90,
+            filename_template: DEFAULT_FILENAME_TEMPLATE.to_string(),
+            trim: TrimSettings::default(),
+            beautify: BeautifySettings::default(),
+            profiles: Vec::new(),
+            default_profile: None,
+            extra: Map::new(),
+        }
+    }
+}
+
+/// Directory of the executable when running portable, i.e. with `--portable` or a
+/// `portable` marker file next to the binary. Portable installs keep config and
+/// screenshots there instead of in the user profile.
+pub fn portable_dir() -> Option<PathBuf> {
+    static PORTABLE_DIR: OnceLock<Option<PathBuf>> = OnceLock::new();
+    PORTABLE_DIR
+        .get_or_init(|| {
+            let exe_dir = std::env::current_exe().ok()?.parent()?.to_path_buf();
+            let requested = std::env::args().any(|arg| arg == "--portable");
+            (requested || exe_dir.join("portable").exists()).then_some(exe_dir)
+        })
+        .clone()
+}
+
+/// A problem with one setting, shown next to that field in the settings window.
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldError {
+    pub field: String, // dotted camelCase path, e.g. "savePath" or "profiles.0.filenameTemplate"
+    pub message: String,
+    /// The value is fine but this machine doesn't match it right now, e.g. a folder that
+    /// isn't writable or a program that isn't installed.
+    #[serde(skip)]
+    pub environment: bool,
+}
+
+impl FieldError {
+    pub fn new(field: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            field: field.into(),
+            message: message.into(),
+            environment: false,
+        }
+    }
+
+    pub fn environment(field: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            environment: true,
+            ..Self::new(field, message)
         }
     }
 }
 
+impl std::fmt::Display for FieldError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.field.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{}: {}", self.field, self.message)
+        }
+    }
+}
+
+/// Rules every saved or reloaded config must satisfy. Checks the settings a capture would
+/// actually use, so problems show up here rather than when a capture fails.
+pub fn validate_config(config: &Config) -> Result<(), Vec<FieldError>> {
+    let mut errors = Vec::new();
+    validate_settings(config, "", &mut errors);
+
+    for (i, profile) in config.profiles.iter().enumerate() {
+        let prefix = format!("profiles.{i}.");
+        if profile.name.trim().is_empty() {
+            errors.push(FieldError::new(format!("{prefix}name"), "Profile name is required"));
+            continue;
+        }
+        if config.profiles[..i].iter().any(|p| p.name == profile.name) {
+            errors.push(FieldError::new(
+                format!("{prefix}name"),
+                format!("Duplicate profile name \"{}\"", profile.name),
+            ));
+        }
+
+        // Problems with inherited values are reported on the base setting they come from
+        let mut profile_errors = Vec::new();
+        let choice = ProfileChoice::Named(profile.name.clone());
+        validate_settings(&config.with_profile(&choice), &prefix, &mut profile_errors);
+        let overridden = collect_overridden(profile);
+        for mut error in profile_errors {
+            let key = &error.field[prefix.len()..];
+            if !overridden.iter().any(|o| key == *o || key.starts_with(&format!("{o}."))) {
+                error.field = key.to_string();
+            }
+            if !errors.iter().any(|e| e.field == error.field) {
+                errors.push(error);
+            }
+        }
+    }
+
+    for (i, program) in config.custom_programs.iter().enumerate() {
+        let field = |name: &str| format!("customPrograms.{i}.{name}");
+        if program.name.trim().is_empty() {
+            errors.push(FieldError::new(field("name"), "Name is required"));
+        }
+        if crate::programs::resolve_program(&program.command).is_none() {
+            errors.push(FieldError::environment(
+                field("command"),
+                format!("Program not found: {}", program.command),
+            ));
+        }
+        if let Err(e) = crate::programs::check_args_template(&program.args) {
+            errors.push(FieldError::new(field("args"), e));
+        }
+        if let Err(e) = check_working_dir(program.working_dir.as_deref()) {
+            errors.push(FieldError::environment(field("workingDir"), e));
+        }
+    }
+
+    // Rule folders must stay inside a save folder the policy fixes
+    let confine = is_policy_locked("savePath")
+        .then(|| crate::storage::base_screenshot_dir(config.save_path.as_deref()).ok())
+        .flatten();
+    for (i, rule) in config.folder_rules.iter().enumerate() {
+        let field = |name: &str| format!("folderRules.{i}.{name}");
+        if let Err(e) = crate::storage::check_rule_folder(&rule.folder, confine.as_deref()) {
+            errors.push(FieldError::new(field("folder"), e));
+        } else if let Err(e) = check_rule_folder(&rule.folder, config.save_path.as_deref()) {
+            errors.push(FieldError::environment(field("folder"), e));
+        }
+        if let Some(name) = &rule.profile {
+            if config.find_profile(name).is_none() {
+                errors.push(FieldError::new(
+                    field("profile"),
+                    format!("No profile named \"{name}\""),
+                ));
+            }
+        }
+        if rule.monitor == Some(0) {
+            errors.push(FieldError::new(field("monitor"), "Monitors count from 1"));
+        }
+        if rule.app.as_deref().is_some_and(|a| a.trim().is_empty()) {
+            errors.push(FieldError::new(
+                field("app"),
+                "Leave unset to match any app",
+            ));
+        }
+    }
+
+    let retention = &config.retention;
+    for (name, value) in [
+        ("maxAgeDays", retention.max_age_days.map(u64::from)),
+        ("maxTotalMb", retention.max_total_mb),
+        ("maxFiles", retention.max_files.map(u64::from)),
+    ] {
+        if value == Some(0) {
+            errors.push(FieldError::new(
+                format!("retention.{name}"),
+                "Must be at least 1; leave unset for no limit",
+            ));
+        }
+    }
+
+    if let Some(name) = &config.default_profile {
+        if config.find_profile(name).is_none() {
+            errors.push(FieldError::new(
+                "defaultProfile",
+                format!("No profile named \"{name}\""),
+            ));
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+fn validate_settings(cfg: &Config, prefix: &str, errors: &mut Vec<FieldError>) {
+    let field = |name: &str| format!("{prefix}{name}");
+
+    if !cfg.copy_to_clipboard && !cfg.auto_open && !cfg.save_locally {
+        errors.push(FieldError::new(
+            field("saveLocally"),
+            "At least one option must be enabled (clipboard, auto-open, or save locally)",
+        ));
+    }
+    if cfg.save_locally {
+        let save_path = cfg.save_path.as_deref().filter(|p| !p.is_empty());
+        if save_path.is_some_and(|p| !Path::new(p).is_absolute()) {
+            errors.push(FieldError::new(field("savePath"), "Must be an absolute path"));
+        } else if let Err(e) = crate::storage::check_screenshot_dir(save_path) {
+            errors.push(FieldError::environment(field("savePath"), e));
+        }
+        if let Err(e) = crate::storage::validate_template(&cfg.filename_template) {
+            errors.push(FieldError::new(field("filenameTemplate"), e));
+        }
+        if let Err(e) = crate::storage::validate_subfolder_template(&cfg.subfolder_template) {
+            errors.push(FieldError::new(field("subfolderTemplate"), e));
+        }
+    }
+    let program = cfg.open_with.program.as_str();
+    if cfg.auto_open && program != "default" && !program.is_empty() {
+        if crate::programs::resolve_program(program).is_none() {
+            errors.push(FieldError::environment(
+                field("openWith.program"),
+                format!("Program not found: {program}"),
+            ));
+        }
+        if program.ends_with(".desktop") && !cfg.open_with.args.trim().is_empty() {
+            errors.push(FieldError::new(
+                field("openWith.args"),
+                "Not used for desktop entries; their Exec line decides the arguments",
+            ));
+        } else if let Err(e) = crate::programs::check_args_template(&cfg.open_with.args) {
+            errors.push(FieldError::new(field("openWith.args"), e));
+        }
+        if let Err(e) = check_working_dir(cfg.open_with.working_dir.as_deref()) {
+            errors.push(FieldError::environment(field("openWith.workingDir"), e));
+        }
+    }
+    if !(1..=100).contains(&cfg.jpeg_quality) {
+        errors.push(FieldError::new(field("jpegQuality"), "Must be between 1 and 100"));
+    }
+    if cfg.beautify.enabled {
+        let options = &cfg.beautify.options;
+        let colors = match &options.background {
+            Background::Solid { color } => vec![color],
+            Background::Gradient { from, to, .. } => vec![from, to],
+        };
+        if let Some(Err(e)) = colors.into_iter().map(|c| parse_hex_color(c)).find(Result::is_err) {
+            errors.push(FieldError::new(field("beautify.background"), e));
+        }
+        if let Some(Err(e)) = options.aspect_ratio.as_deref().map(parse_aspect_ratio) {
+            errors.push(FieldError::new(field("beautify.aspectRatio"), e));
+        }
+    }
+}
+
+/// Whether captures could be saved in a rule's folder on this machine.
+fn check_rule_folder(folder: &str, save_path: Option<&str>) -> Result<(), String> {
+    let path = crate::storage::base_screenshot_dir(save_path)?.join(folder);
+    crate::storage::check_save_dir(&path)
+}
+
+fn check_working_dir(dir: Option<&str>) -> Result<(), String> {
+    match dir {
+        Some(dir) if !dir.is_empty() && !Path::new(dir).is_dir() => {
+            Err(format!("Folder not found: {dir}"))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// camelCase names of the fields a profile sets.
+fn collect_overridden(profile: &Profile) -> Vec<&'static str> {
+    // The output toggles are validated together, so changing any of them counts for all
+    let outputs = profile.copy_to_clipboard.is_some()
+        || profile.auto_open.is_some()
+        || profile.save_locally.is_some();
+    [
+        ("copyToClipboard", outputs),
+        ("autoOpen", outputs),
+        ("saveLocally", outputs),
+        ("savePath", profile.save_path.is_some()),
+        ("openWith", profile.open_with.is_some()),
+        ("jpegQuality", profile.jpeg_quality.is_some()),
+        ("filenameTemplate", profile.filename_template.is_some()),
+        ("subfolderTemplate", profile.subfolder_template.is_some()),
+        ("beautify", profile.beautify.is_some()),
+    ]
+    .into_iter()
+    .filter(|(_, set)| *set)
+    .map(|(name, _)| name)
+    .collect()
+}
+
 pub fn get_config_dir() -> Result<PathBuf, String> {
-    let mut path = dirs::config_dir().ok_or("Could not find config directory")?;
-    path.push("opencap");
+    let path = match portable_dir() {
+        Some(dir) => dir.join("config"),
+        None => {
+            let mut path = dirs::config_dir().ok_or("Could not find config directory")?;
+            path.push("opencap");
+            path
+        }
+    };
     std::fs::create_dir_all(&path)
         .map_err(|e| format!("Failed to create config dir: {e}"))?;
     Ok(path)
 }
 
 pub fn get_config_path() -> Result<PathBuf, String> {
-    Ok(get_config_dir()?.join("config.json"))
+    Ok(get_config_dir()?.join(CONFIG_FILE))
+}
+
+/// Directory holding the administrator-managed `defaults.json` and `policy.json`.
+pub fn get_system_config_dir() -> Option<PathBuf> {
+    #[cfg(target_os = "windows")]
+    {
+        std::env::var_os("ProgramData").map(|p| PathBuf::from(p).join("OpenCap"))
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        Some(PathBuf::from("/Library/Application Support/OpenCap"))
+    }
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    {
+        Some(PathBuf::from("/etc/opencap"))
+    }
+
+    #[cfg(not(any(target_os = "windows", unix)))]
+    {
+        None
+    }
+}
+
+/// The layers that win over the user's settings: environment and `--set` overrides, then
+/// the machine policy, each with values that don't parse left out.
+fn enforced_layer(warnings: &mut Vec<String>) -> Map<String, Value> {
+    let (mut layer, _) = salvage_layer(
+        read_override_layer(warnings),
+        "an environment or --set override",
+        warnings,
+    );
+    if let Some(policy) = read_system_layer(POLICY_FILE, warnings) {
+        merge_into(&mut layer, &salvage_layer(policy, POLICY_FILE, warnings).0);
+    }
+    layer
+}
+
+/// The settings in one layer that parse on their own, and whether any were dropped. Each
+/// dropped one is reported as coming from `source`.
+fn salvage_layer(
+    layer: Map<String, Value>,
+    source: &str,
+    warnings: &mut Vec<String>,
+) -> (Map<String, Value>, bool) {
+    let fits = |doc: &Map<String, Value>| {
+        serde_json::from_value::<Config>(Value::Object(doc.clone())).is_ok()
+    };
+    if fits(&layer) {
+        return (layer, false);
+    }
+    let mut dropped = Vec::new();
+    let salvaged = salvage(layer, "", &fits, &mut dropped);
+    warnings.extend(
+        dropped
+            .into_iter()
+            .map(|path| format!("Ignored invalid value for \"{path}\" in {source}")),
+    );
+    (salvaged, true)
+}
+
+/// Read a machine-wide layer. Layers always use the current format, so no migration.
+fn read_system_layer(name: &str, warnings: &mut Vec<String>) -> Option<Map<String, Value>> {
+    let path = get_system_config_dir()?.join(name);
+    if !path.exists() {
+        return None;
+    }
+    let parsed = std::fs::read_to_string(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|c| serde_json::from_str::<Value>(&c).map_err(|e| e.to_string()));
+    match parsed {
+        Ok(Value::Object(mut doc)) => {
+            // Admin files are usually unversioned; bring old keys up to date like user files
+            let version = doc
+                .remove("version")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(0) as usize;
+            for migrate in MIGRATIONS.iter().skip(version) {
+                migrate(&mut doc);
+            }
+            Some(doc)
+        }
+        Ok(_) => {
+            warnings.push(format!("Ignoring {}: not a JSON object", path.display()));
+            None
+        }
+        Err(e) => {
+            warnings.push(format!("Ignoring {}: {e}", path.display()));
+            None
+        }
+    }
+}
+
+/// Per-invocation overrides from `OPENCAP_*` environment variables and `--set key=value`
+/// arguments. Keys are config keys in either case; `__` in a variable name and `.` in a
+/// `--set` key reach nested settings, e.g. `OPENCAP_TRIM__ENABLED` or `--set trim.enabled=true`.
+/// Values are parsed as JSON where that fits the setting, otherwise taken as plain strings.
+fn read_override_layer(warnings: &mut Vec<String>) -> Map<String, Value> {
+    let mut raw: Vec<(String, String, bool)> = std::env::vars()
+        .filter_map(|(name, value)| {
+            let key = name.strip_prefix("OPENCAP_")?.replace("__", ".");
+            Some((key, value, false))
+        })
+        .collect();
+    let args: Vec<String> = std::env::args().collect();
+    for pair in args.windows(2).filter(|w| w[0] == "--set") {
+        match pair[1].split_once('=') {
+            Some((key, value)) => raw.push((key.to_string(), value.to_string(), true)),
+            None => warnings.push(format!("Ignoring --set {}: expected key=value", pair[1])),
+        }
+    }
+
+    let known = to_doc(&Config::default());
+    let mut layer = Map::new();
+    for (key, value, explicit) in raw {
+        let mut path = key
+            .split('.')
+            .map(snake_to_camel)
+            .collect::<Vec<_>>()
+            .join(".");
+        if let Some((_, new)) = RENAMED_KEYS.iter().find(|(old, _)| *old == path) {
+            path = new.to_string();
+        }
+        if get_path(&known, &path).is_none() {
+            // Unrelated OPENCAP_* variables are fine; a mistyped --set is not
+            if explicit {
+                warnings.push(format!("Ignoring --set {key}: unknown setting"));
+            }
+            continue;
+        }
+        let top = path.split('.').next().unwrap_or_default();
+        if NOT_OVERRIDABLE.contains(&top) {
+            // OPENCAP_VERSION and the like are usually meant for something else
+            if explicit {
+                warnings.push(format!("Ignoring --set {key}: {top} can't be overridden"));
+            }
+            continue;
+        }
+
+        let parsed = serde_json::from_str::<Value>(&value).ok();
+        let fits = parsed.as_ref().is_some_and(|v| {
+            let mut single = Map::new();
+            set_path(&mut single, &path, Some(v.clone()));
+            serde_json::from_value::<Config>(Value::Object(single)).is_ok()
+        });
+        let value = match parsed {
+            Some(v) if fits => v,
+            _ => Value::String(value),
+        };
+        set_path(&mut layer, &path, Some(value));
+    }
+    layer
+}
+
+/// `save_path` and `SAVE_PATH` become `savePath`; camelCase passes through unchanged.
+fn snake_to_camel(segment: &str) -> String {
+    if !segment.contains('_') && segment.chars().any(|c| c.is_lowercase()) {
+        return segment.to_string();
+    }
+    let mut out = String::new();
+    for (i, word) in segment.split('_').filter(|w| !w.is_empty()).enumerate() {
+        let word = word.to_lowercase();
+        let mut chars = word.chars();
+        if i > 0 {
+            if let Some(first) = chars.next() {
+                out.extend(first.to_uppercase());
+            }
+        }
+        out.extend(chars);
+    }
+    out
+}
+
+/// Dotted paths of settings the user can't change from the settings window: those fixed by
+/// the machine policy and those overridden for this invocation, e.g. `savePath` or `trim.enabled`.
+pub fn locked_fields() -> Vec<String> {
+    let mut fields = Vec::new();
+    collect_leaf_paths(&read_override_layer(&mut Vec::new()), "", &mut fields);
+    if let Some(policy) = read_system_layer(POLICY_FILE, &mut Vec::new()) {
+        collect_leaf_paths(&policy, "", &mut fields);
+    }
+    fields.sort();
+    fields.dedup();
+    fields
+}
+
+/// Whether the machine policy fixes `field`, a dotted path such as `savePath`.
+pub fn is_policy_locked(field: &str) -> bool {
+    read_system_layer(POLICY_FILE, &mut Vec::new())
+        .is_some_and(|policy| get_path(&policy, field).is_some())
+}
+
+/// Locked fields whose value in `config` differs from what is enforced, including fields
+/// a profile sets to something other than what the policy fixes, e.g. `profiles.0.savePath`.
+pub fn policy_violations(config: &Config) -> Vec<String> {
+    let locked = locked_fields();
+    if locked.is_empty() {
+        return locked;
+    }
+    let wanted = to_doc(config);
+    let enforced = to_doc(&load_config());
+    let mut violations: Vec<String> = locked
+        .into_iter()
+        .filter(|field| get_path(&wanted, field) != get_path(&enforced, field))
+        .collect();
+
+    let mut policy_fields = Vec::new();
+    if let Some(policy) = read_system_layer(POLICY_FILE, &mut Vec::new()) {
+        collect_leaf_paths(&policy, "", &mut policy_fields);
+    }
+    for (i, profile) in config.profiles.iter().enumerate() {
+        let Ok(Value::Object(profile)) = serde_json::to_value(profile) else {
+            continue;
+        };
+        for field in &policy_fields {
+            // Unset (null) profile values inherit the locked base value
+            let set = get_path(&profile, field).filter(|v| !v.is_null());
+            if set.is_some_and(|v| Some(v) != get_path(&enforced, field)) {
+                violations.push(format!("profiles.{i}.{field}"));
+            }
+        }
+    }
+    violations
+}
+
+fn to_doc(config: &Config) -> Map<String, Value> {
+    match serde_json::to_value(config) {
+        Ok(Value::Object(doc)) => doc,
+        _ => Map::new(),
+    }
+}
+
+/// Recursively overlay `overlay` onto `base`; nested objects merge, everything else replaces.
+fn merge_into(base: &mut Map<String, Value>, overlay: &Map<String, Value>) {
+    for (key, value) in overlay {
+        match (base.get_mut(key), value) {
+            (Some(Value::Object(b)), Value::Object(o)) => merge_into(b, o),
+            _ => {
+                base.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
+fn collect_leaf_paths(doc: &Map<String, Value>, prefix: &str, out: &mut Vec<String>) {
+    for (key, value) in doc {
+        let path = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{prefix}.{key}")
+        };
+        match value {
+            Value::Object(child) if !child.is_empty() => collect_leaf_paths(child, &path, out),
+            _ => out.push(path),
+        }
+    }
+}
+
+fn get_path<'a>(doc: &'a Map<String, Value>, path: &str) -> Option<&'a Value> {
+    let mut parts = path.split('.');
+    let mut cur = doc.get(parts.next()?)?;
+    for part in parts {
+        cur = cur.as_object()?.get(part)?;
+    }
+    Some(cur)
+}
+
+/// Set or, with `None`, remove the value at a dotted path.
+fn set_path(doc: &mut Map<String, Value>, path: &str, value: Option<Value>) {
+    match path.split_once('.') {
+        None => {
+            match value {
+                Some(v) => doc.insert(path.to_string(), v),
+                None => doc.remove(path),
+            };
+        }
+        Some((head, rest)) => match doc.get_mut(head) {
+            Some(Value::Object(child)) => set_path(child, rest, value),
+            _ => {
+                if let Some(v) = value {
+                    let mut child = Map::new();
+                    set_path(&mut child, rest, Some(v));
+                    doc.insert(head.to_string(), Value::Object(child));
+                }
+            }
+        },
+    }
+}
+
+/// JSON Schema for `config.json`, with descriptions, allowed values and defaults, so editors
+/// and tooling can check hand-edited files.
+pub fn config_schema() -> Value {
+    schemars::schema_for!(Config).to_value()
+}
+
+/// A loaded config plus anything the user should know about how it was read.
+pub struct LoadedConfig {
+    pub config: Config,
+    pub warnings: Vec<String>,
+    /// Some of the file's values were discarded while loading
+    pub lossy: bool,
 }
 
 pub fn load_config() -> Config {
-    match get_config_path() {
+    load_config_report().config
+}
+
+/// Load the effective config: machine defaults, then the user's file, then environment and
+/// `--set` overrides, then the machine policy. Older user files are migrated and every field that still parses is kept.
+/// A user file that can't be used at all is copied aside and replaced by the last backup
+/// (or defaults if there is none), so a later save never destroys the user's only copy.
+/// Each layer is checked on its own, so a bad value in another layer only drops that value
+/// and never marks the user's file as broken.
+pub fn load_config_report() -> LoadedConfig {
+    let mut warnings = Vec::new();
+    let user_path = get_config_path();
+
+    let mut doc = read_system_layer(SYSTEM_DEFAULTS_FILE, &mut warnings)
+        .map(|layer| salvage_layer(layer, SYSTEM_DEFAULTS_FILE, &mut warnings).0)
+        .unwrap_or_default();
+    let mut lossy = false;
+    match &user_path {
         Ok(path) => {
-            if path.exists() {
-                match std::fs::read_to_string(&path) {
-                    Ok(contents) => {
-                        match serde_json::from_str(&contents) {
-                            Ok(config) => config,
-                            Err(e) => {
-                                log::warn!("Failed to parse config, using defaults: {e}");
-                                Config::default()
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        log::warn!("Failed to read config file, using defaults: {e}");
-                        Config::default()
-                    }
+            let (user, dropped) = salvage_layer(
+                load_user_doc(path, &mut warnings),
+                CONFIG_FILE,
+                &mut warnings,
+            );
+            lossy = dropped;
+            merge_into(&mut doc, &user);
+        }
+        Err(e) => warnings.push(format!("Failed to get config path, using defaults: {e}")),
+    }
+    merge_into(&mut doc, &enforced_layer(&mut warnings));
+
+    // The layers parse on their own, so this only drops values that clash once combined
+    let mut loaded = build_config(doc, warnings);
+    loaded.lossy = lossy;
+    if loaded.lossy {
+        if let Some(kept) = user_path.ok().and_then(|p| preserve_original(&p)) {
+            loaded
+                .warnings
+                .push(format!("The original file was kept at {}", kept.display()));
+        }
+    }
+    for warning in &loaded.warnings {
+        log::warn!("{warning}");
+    }
+    loaded
+}
+
+/// The user's own settings document, falling back to the backup and then to nothing. Only
+/// contents that were read and can't be used are replaced; a file that can't be read at the
+/// moment (no permission, locked by another program) is left alone and defaults are used
+/// for this run.
+fn load_user_doc(path: &Path, warnings: &mut Vec<String>) -> Map<String, Value> {
+    if !path.exists() {
+        return Map::new();
+    }
+    let contents = match std::fs::read(path) {
+        Ok(contents) => contents,
+        Err(e) => {
+            warnings.push(format!(
+                "Failed to read config file: {e}; using defaults without changing the file"
+            ));
+            return Map::new();
+        }
+    };
+
+    match decode_config_doc(&contents) {
+        Ok((doc, doc_warnings)) => {
+            warnings.extend(doc_warnings);
+            doc
+        }
+        Err(e) => {
+            let kept = preserve_original(path)
+                .map(|p| format!(" The original file was kept at {}", p.display()))
+                .unwrap_or_default();
+            match restore_backup(path) {
+                Ok((doc, doc_warnings)) => {
+                    warnings.push(format!("{e}; restored settings from the backup.{kept}"));
+                    warnings.extend(doc_warnings);
+                    doc
+                }
+                Err(_) => {
+                    warnings.push(format!("{e}; using defaults.{kept}"));
+                    Map::new()
                 }
-            } else {
-                Config::default()
             }
         }
-        Err(e) => {
-            log::warn!("Failed to get config path, using defaults: {e}");
-            Config::default()
+    }
+}
+
+fn read_config_doc(path: &Path) -> Result<(Map<String, Value>, Vec<String>), String> {
+    let contents = std::fs::read(path).map_err(|e| format!("Failed to read config file: {e}"))?;
+    decode_config_doc(&contents)
+}
+
+/// Parse a config file's bytes; text that isn't UTF-8 is as unusable as invalid JSON.
+fn decode_config_doc(contents: &[u8]) -> Result<(Map<String, Value>, Vec<String>), String> {
+    std::str::from_utf8(contents)
+        .map_err(|e| e.to_string())
+        .and_then(parse_config_doc)
+        .map_err(|e| format!("Failed to parse config: {e}"))
+}
+
+fn backup_path(path: &Path) -> PathBuf {
+    path.with_extension("json.bak")
+}
+
+/// Load `config.json.bak` and put it back in place of the unusable main file.
+fn restore_backup(path: &Path) -> Result<(Map<String, Value>, Vec<String>), String> {
+    let backup = backup_path(path);
+    let loaded = read_config_doc(&backup)?;
+    let contents = std::fs::read(&backup).map_err(|e| format!("Failed to read backup: {e}"))?;
+    if let Err(e) = write_atomic(path, &contents) {
+        log::error!("Failed to restore config from backup: {e}");
+    }
+    Ok(loaded)
+}
+
+/// Parse a config document of any known version.
+pub fn parse_config(contents: &str) -> Result<LoadedConfig, String> {
+    let (doc, warnings) = parse_config_doc(contents)?;
+    Ok(build_config(doc, warnings))
+}
+
+/// Parse JSON and migrate it to the current version, without deserializing it yet.
+fn parse_config_doc(contents: &str) -> Result<(Map<String, Value>, Vec<String>), String> {
+    let value: Value = serde_json::from_str(contents).map_err(|e| format!("Invalid JSON: {e}"))?;
+    let Value::Object(mut doc) = value else {
+        return Err("Config must be a JSON object".into());
+    };
+
+    let mut warnings = Vec::new();
+    let version = doc.get("version").and_then(Value::as_u64).unwrap_or(0) as usize;
+    if version > CONFIG_VERSION as usize {
+        warnings.push(format!(
+            "Config was written by a newer OpenCap (format v{version}); unknown settings are kept but ignored"
+        ));
+    } else {
+        for migrate in &MIGRATIONS[version..] {
+            migrate(&mut doc);
         }
+        doc.insert("version".into(), CONFIG_VERSION.into());
     }
+    Ok((doc, warnings))
 }
 
+/// Deserialize a document, keeping every field that parses so only the broken ones fall
+/// back to defaults.
+fn build_config(doc: Map<String, Value>, mut warnings: Vec<String>) -> LoadedConfig {
+    if let Ok(config) = serde_json::from_value(Value::Object(doc.clone())) {
+        return LoadedConfig {
+            config,
+            warnings,
+            lossy: false,
+        };
+    }
+
+    let fits = |doc: &Map<String, Value>| {
+        serde_json::from_value::<Config>(Value::Object(doc.clone())).is_ok()
+    };
+    let mut dropped = Vec::new();
+    let salvaged = salvage(doc, "", &fits, &mut dropped);
+    warnings.extend(
+        dropped
+            .into_iter()
+            .map(|path| format!("Ignored invalid value for \"{path}\", using the default")),
+    );
+    LoadedConfig {
+        config: serde_json::from_value(Value::Object(salvaged)).unwrap_or_default(),
+        warnings,
+        lossy: true,
+    }
+}
+
+/// The fields of `doc` that `fits` accepts, added one at a time. Nested settings objects are
+/// salvaged field by field, so a bad `beautify.padding` only resets the padding. Objects
+/// that only parse whole, such as a background, are kept or dropped whole. The dotted
+/// paths of dropped fields go to `dropped`.
+fn salvage(
+    doc: Map<String, Value>,
+    prefix: &str,
+    fits: &dyn Fn(&Map<String, Value>) -> bool,
+    dropped: &mut Vec<String>,
+) -> Map<String, Value> {
+    let mut salvaged = Map::new();
+    for (key, value) in doc {
+        let path = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{prefix}.{key}")
+        };
+        let with = |salvaged: &Map<String, Value>, value: Value| {
+            let mut candidate = salvaged.clone();
+            candidate.insert(key.clone(), value);
+            candidate
+        };
+        if fits(&with(&salvaged, value.clone())) {
+            salvaged.insert(key, value);
+            continue;
+        }
+        if let Value::Object(child) = value {
+            let mut child_dropped = Vec::new();
+            let child_fits = |child: &Map<String, Value>| {
+                fits(&with(&salvaged, Value::Object(child.clone())))
+            };
+            let child = salvage(child, &path, &child_fits, &mut child_dropped);
+            if child_fits(&child) {
+                dropped.extend(child_dropped);
+                salvaged.insert(key, Value::Object(child));
+                continue;
+            }
+        }
+        dropped.push(path);
+    }
+    salvaged
+}
+
+/// Unversioned files could store "" to mean "use the default" for these fields.
+fn migrate_v0_to_v1(doc: &mut Map<String, Value>) {
+    if doc.get("savePath").and_then(Value::as_str) == Some("") {
+        doc.insert("savePath".into(), Value::Null);
+    }
+    if doc.get("openWithProgram").and_then(Value::as_str) == Some("") {
+        doc.insert("openWithProgram".into(), "default".into());
+    }
+}
+
+/// `openWithProgram` became `openWith.program`, next to the new argument template.
+fn migrate_v1_to_v2(doc: &mut Map<String, Value>) {
+    fn move_program(doc: &mut Map<String, Value>) {
+        // A null profile value means "inherit", so only strings carry over
+        if let Some(Value::String(program)) = doc.remove("openWithProgram") {
+            doc.entry("openWith")
+                .or_insert_with(|| serde_json::json!({ "program": program }));
+        }
+    }
+    move_program(doc);
+    if let Some(Value::Array(profiles)) = doc.get_mut("profiles") {
+        for profile in profiles.iter_mut().filter_map(Value::as_object_mut) {
+            move_program(profile);
+        }
+    }
+}
+
+/// Copy a config file that couldn't be fully loaded next to itself, once per distinct content.
+fn preserve_original(path: &Path) -> Option<PathBuf> {
+    let kept = path.with_extension("json.invalid");
+    let same = std::fs::read(path).ok()? == std::fs::read(&kept).unwrap_or_default();
+    if !same {
+        if let Err(e) = std::fs::copy(path, &kept) {
+            log::error!("Failed to preserve unreadable config: {e}");
+            return None;
+        }
+    }
+    Some(kept)
+}
+
+/// Write the config atomically, first rotating the current file into `config.json.bak`.
+/// Only the user's own layer is written: locked fields keep the user's own value on disk,
+/// so lifting the policy or dropping an override restores it, and values that merely
+/// repeat `defaults.json` are left out, so later changes to the machine defaults still apply.
 pub fn save_config(config: &Config) -> Result<(), String> {
     let path = get_config_path()?;
-    let contents = serde_json::to_string_pretty(config)
-        .map_err(|e| format!("Failed to serialize config: {e}"))?;
-    std::fs::write(&path, contents)
-        .map_err(|e| format!("Failed to write config file: {e}"))?;
+    let locked = locked_fields();
+    let defaults = read_system_layer(SYSTEM_DEFAULTS_FILE, &mut Vec::new());
+    let contents = if locked.is_empty() && defaults.is_none() {
+        serde_json::to_string_pretty(config)
+    } else {
+        let mut doc = to_doc(config);
+        let stored = read_config_doc(&path)
+            .map(|(doc, _)| doc)
+            .unwrap_or_default();
+        for field in &locked {
+            set_path(&mut doc, field, get_path(&stored, field).cloned());
+        }
+        if let Some(defaults) = &defaults {
+            let mut fields = Vec::new();
+            collect_leaf_paths(defaults, "", &mut fields);
+            for field in fields {
+                // A value the user set themselves stays, even if it matches today's default
+                if get_path(&stored, &field).is_none()
+                    && get_path(&doc, &field) == get_path(defaults, &field)
+                {
+                    set_path(&mut doc, &field, None);
+                }
+            }
+        }
+        serde_json::to_string_pretty(&doc)
+    }
+    .map_err(|e| format!("Failed to serialize config: {e}"))?;
+
+    // Only a file that still loads cleanly may replace the backup
+    if let Ok(current) = std::fs::read(&path) {
+        let usable = std::str::from_utf8(&current)
+            .ok()
+            .and_then(|c| parse_config(c).ok())
+            .is_some_and(|loaded| !loaded.lossy);
+        if usable {
+            if let Err(e) = write_atomic(&backup_path(&path), &current) {
+                log::warn!("Failed to back up config: {e}");
+            }
+        }
+    }
+
+    write_atomic(&path, contents.as_bytes())
+        .map_err(|e| format!("Failed to write config file: {e}"))
+}
+
+/// Write to a temporary sibling, fsync it and rename it over `path`, so readers see either
+/// the old file or the complete new one, never a truncated mix.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> Result<(), String> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    let result = (|| {
+        let mut file = std::fs::File::create(&tmp)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        std::fs::rename(&tmp, path)
+    })();
+    if let Err(e) = result {
+        let _ = std::fs::remove_file(&tmp);
+        return Err(e.to_string());
+    }
+
+    // Persist the rename itself; directories can't be opened for syncing on Windows
+    #[cfg(unix)]
+    if let Some(dir) = path.parent() {
+        if let Ok(dir) = std::fs::File::open(dir) {
+            let _ = dir.sync_all();
+        }
+    }
     Ok(())
 }
+
+/// Marks a file as an OpenCap settings bundle rather than arbitrary JSON.
+const BUNDLE_FORMAT: &str = "opencap-settings";
+
+/// The user's settings in one shareable file, see `export_bundle`.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SettingsBundle<C> {
+    format: String,
+    exported_at: String,
+    config: C,
+}
+
+impl<C> SettingsBundle<C> {
+    fn new(config: C) -> Self {
+        Self {
+            format: BUNDLE_FORMAT.to_string(),
+            exported_at: chrono::Local::now().to_rfc3339(),
+            config,
+        }
+    }
+}
+
+/// What importing a bundle would do, for the settings window to confirm.
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportPreview {
+    pub changes: Vec<ConfigChange>,
+    pub warnings: Vec<String>,
+}
+
+/// One setting an import would change.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigChange {
+    pub field: String, // dotted camelCase path
+    pub old: Option<Value>,
+    pub new: Option<Value>,
+}
+
+/// Write the current settings, profiles included, to `path`. Settings locked by policy or
+/// overridden for this run belong to this machine and are left out.
+pub fn export_bundle(path: &Path) -> Result<(), String> {
+    let config = load_config();
+    let locked = locked_fields();
+    // Like `save_config`, only go through a document when something has to be removed
+    let json = if locked.is_empty() {
+        serde_json::to_string_pretty(&SettingsBundle::new(&config))
+    } else {
+        let mut doc = to_doc(&config);
+        for field in &locked {
+            set_path(&mut doc, field, None);
+        }
+        serde_json::to_string_pretty(&SettingsBundle::new(Value::Object(doc)))
+    }
+    .map_err(|e| format!("Failed to serialize settings: {e}"))?;
+    write_atomic(path, json.as_bytes())
+        .map_err(|e| format!("Failed to write {}: {e}", path.display()))
+}
+
+/// Read a bundle written by `export_bundle` and return the settings importing it would
+/// produce, with warnings for the user. Unlike loading `config.json`, nothing invalid is
+/// silently dropped: any value that doesn't fit the config is an error. Settings from a
+/// newer version that this one doesn't know are kept unused, as in `config.json`. Settings
+/// missing from the bundle, or locked on this machine, keep their current value.
+pub fn read_bundle(path: &Path) -> Result<(Config, Vec<String>), String> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+    let bundle: SettingsBundle<Value> = serde_json::from_str(&contents)
+        .map_err(|e| format!("Not an OpenCap settings file: {e}"))?;
+    if bundle.format != BUNDLE_FORMAT {
+        return Err(format!("Not an OpenCap settings file (format \"{}\")", bundle.format));
+    }
+
+    let (mut imported, warnings) = parse_config_doc(&bundle.config.to_string())?;
+    let checked = build_config(imported.clone(), warnings);
+    if checked.lossy {
+        return Err(format!("Invalid settings file: {}", checked.warnings.join("; ")));
+    }
+
+    // This file stays in this version's format, whatever the bundle's
+    imported.remove("version");
+    for field in locked_fields() {
+        set_path(&mut imported, &field, None);
+    }
+    let mut doc = to_doc(&load_config());
+    merge_into(&mut doc, &imported);
+    let config = serde_json::from_value(Value::Object(doc))
+        .map_err(|e| format!("Invalid settings file: {e}"))?;
+    Ok((config, checked.warnings))
+}
+
+/// Validate the settings from `read_bundle` and save them as the user's config.
+pub fn import_bundle(path: &Path) -> Result<(), Vec<FieldError>> {
+    let (config, _) = read_bundle(path).map_err(|e| vec![FieldError::new("", e)])?;
+    validate_config(&config)?;
+    save_config(&config).map_err(|e| vec![FieldError::new("", e)])
+}
+
+/// Settings that differ between `old` and `new`, for previewing an import.
+pub fn diff_configs(old: &Config, new: &Config) -> Vec<ConfigChange> {
+    let (old, new) = (to_doc(old), to_doc(new));
+    let mut fields = Vec::new();
+    collect_leaf_paths(&old, "", &mut fields);
+    collect_leaf_paths(&new, "", &mut fields);
+    fields.sort();
+    fields.dedup();
+
+    fields
+        .into_iter()
+        .filter(|field| field != "version")
+        .filter_map(|field| {
+            let (a, b) = (get_path(&old, &field), get_path(&new, &field));
+            (a != b).then(|| ConfigChange {
+                old: a.cloned(),
+                new: b.cloned(),
+                field,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shipped_schema_is_current() {
+        let shipped: Value = serde_json::from_str(include_str!("../../docs/config.schema.json"))
+            .expect("docs/config.schema.json is valid JSON");
+        assert!(
+            shipped == config_schema(),
+            "docs/config.schema.json is out of date; regenerate it with `opencap config-schema`"
+        );
+    }
+}
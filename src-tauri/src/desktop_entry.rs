@@ -0,0 +1,546 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// An application from a freedesktop `.desktop` file, as used by Linux desktops to list
+/// installed programs. See the Desktop Entry Specification for the format and field codes.
+#[derive(Debug, Clone)]
+pub struct DesktopEntry {
+    pub path: PathBuf,
+    pub name: String,
+    pub exec: String,
+    pub icon: Option<String>,
+    pub mime_types: Vec<String>,
+    pub no_display: bool,
+    pub hidden: bool,
+    pub try_exec: Option<String>,
+}
+
+impl DesktopEntry {
+    /// Read an application entry, with `Name` and `Icon` in the user's language where the
+    /// file provides one. Returns `None` for other entry types or unreadable files.
+    pub fn load(path: &Path) -> Option<Self> {
+        let contents = std::fs::read_to_string(path).ok()?;
+        Self::parse(path, &contents, &user_locales())
+    }
+
+    fn parse(path: &Path, contents: &str, locales: &[String]) -> Option<Self> {
+        let mut in_main_group = false;
+        let mut keys = HashMap::new();
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if line.starts_with('[') {
+                in_main_group = line == "[Desktop Entry]";
+                continue;
+            }
+            if !in_main_group {
+                continue;
+            }
+            if let Some((key, value)) = line.split_once('=') {
+                keys.entry(key.trim().to_string())
+                    .or_insert_with(|| unescape(value.trim()));
+            }
+        }
+
+        if keys.get("Type").map(String::as_str) != Some("Application") {
+            return None;
+        }
+        let localized = |key: &str| {
+            locales
+                .iter()
+                .find_map(|l| keys.get(&format!("{key}[{l}]")))
+                .or_else(|| keys.get(key))
+                .cloned()
+        };
+        let flag = |key: &str| keys.get(key).is_some_and(|v| v == "true");
+
+        Some(Self {
+            path: path.to_path_buf(),
+            name: localized("Name")?,
+            exec: keys.get("Exec")?.clone(),
+            icon: localized("Icon").filter(|i| !i.is_empty()),
+            mime_types: keys
+                .get("MimeType")
+                .map(|v| split_list(v))
+                .unwrap_or_default(),
+            no_display: flag("NoDisplay"),
+            hidden: flag("Hidden"),
+            try_exec: keys.get("TryExec").cloned(),
+        })
+    }
+
+    pub fn packaging(&self) -> Packaging {
+        let exe = self.executable().unwrap_or_default();
+        if Path::new(&exe).file_name().is_some_and(|n| n == "flatpak") {
+            Packaging::Flatpak
+        } else if self.path.starts_with("/var/lib/snapd/desktop") || self.exec.contains("/snap/bin/") {
+            Packaging::Snap
+        } else {
+            Packaging::Native
+        }
+    }
+
+    /// The program `Exec` runs, as written in the file.
+    pub fn executable(&self) -> Option<String> {
+        split_exec(&self.exec)?.into_iter().next()
+    }
+
+    /// Command line for opening `file`, with the `Exec` field codes expanded. Entries that
+    /// take no file argument still get the file appended, since that's why we launch them.
+    pub fn command_for(&self, file: &Path) -> Option<Vec<String>> {
+        let file = file.to_string_lossy();
+        let mut args = Vec::new();
+        let mut got_file = false;
+        for arg in split_exec(&self.exec)? {
+            match arg.as_str() {
+                "%f" | "%F" | "%u" | "%U" => {
+                    if !got_file {
+                        args.push(file.to_string());
+                        got_file = true;
+                    }
+                }
+                "%i" => {
+                    if let Some(icon) = &self.icon {
+                        args.push("--icon".to_string());
+                        args.push(icon.clone());
+                    }
+                }
+                _ => {
+                    let mut out = String::new();
+                    let mut chars = arg.chars();
+                    while let Some(c) = chars.next() {
+                        if c != '%' {
+                            out.push(c);
+                            continue;
+                        }
+                        match chars.next() {
+                            Some('%') => out.push('%'),
+                            Some('f' | 'F' | 'u' | 'U') => {
+                                out.push_str(&file);
+                                got_file = true;
+                            }
+                            Some('c') => out.push_str(&self.name),
+                            Some('k') => out.push_str(&self.path.to_string_lossy()),
+                            // Deprecated or unknown codes are dropped
+                            _ => {}
+                        }
+                    }
+                    if !out.is_empty() {
+                        args.push(out);
+                    }
+                }
+            }
+        }
+        if args.is_empty() {
+            return None;
+        }
+        if !got_file {
+            args.push(file.to_string());
+        }
+        if self.packaging() == Packaging::Flatpak {
+            forward_flatpak_file(&mut args, &file);
+        }
+        Some(args)
+    }
+}
+
+/// How an application was installed, which decides how it is launched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Packaging {
+    Native,
+    Flatpak,
+    Snap,
+}
+
+/// Sandboxed Flatpak apps can only open files passed through the document portal. Exported
+/// entries already ask for that with `--file-forwarding` and `@@u ... @@` around the file
+/// code; hand-written ones may not, so add it.
+fn forward_flatpak_file(args: &mut Vec<String>, file: &str) {
+    if args.iter().any(|a| a == "--file-forwarding") {
+        return;
+    }
+    let Some(run) = args.iter().position(|a| a == "run") else {
+        return;
+    };
+    let Some(pos) = args.iter().rposition(|a| a == file) else {
+        return;
+    };
+    args.splice(pos..=pos, ["@@".to_string(), file.to_string(), "@@".to_string()]);
+    args.insert(run + 1, "--file-forwarding".to_string());
+}
+
+/// Every application entry in the user's data directories. Entries in earlier directories
+/// shadow later ones with the same desktop file ID, as the spec requires.
+pub fn installed_applications() -> Vec<DesktopEntry> {
+    let mut seen = HashSet::new();
+    let mut entries = Vec::new();
+    for dir in data_dirs() {
+        let apps = dir.join("applications");
+        let mut files = Vec::new();
+        collect_desktop_files(&apps, &mut files);
+        files.sort();
+        for file in files {
+            let Some(id) = desktop_file_id(&apps, &file) else {
+                continue;
+            };
+            if seen.insert(id) {
+                entries.extend(DesktopEntry::load(&file));
+            }
+        }
+    }
+    entries
+}
+
+/// Resolve an icon name to a file from the hicolor theme or pixmaps, preferring larger
+/// bitmaps and then scalable icons.
+pub fn resolve_icon(icon: &str) -> Option<PathBuf> {
+    let path = Path::new(icon);
+    if path.is_absolute() {
+        return path.exists().then(|| path.to_path_buf());
+    }
+    const SIZES: &[&str] = &["256x256", "128x128", "96x96", "64x64", "48x48", "32x32", "scalable"];
+    for dir in data_dirs() {
+        for size in SIZES {
+            let base = dir.join("icons/hicolor").join(size).join("apps");
+            for ext in ["png", "svg"] {
+                let candidate = base.join(format!("{icon}.{ext}"));
+                if candidate.exists() {
+                    return Some(candidate);
+                }
+            }
+        }
+    }
+    ["png", "svg", "xpm"]
+        .iter()
+        .map(|ext| PathBuf::from(format!("/usr/share/pixmaps/{icon}.{ext}")))
+        .find(|p| p.exists())
+}
+
+/// `$XDG_DATA_HOME` followed by `$XDG_DATA_DIRS`, with the spec's defaults, then the
+/// Flatpak and Snap export directories. Sessions normally list those in `XDG_DATA_DIRS`
+/// already, but not when OpenCap is started outside one, e.g. by a hotkey daemon.
+pub fn data_dirs() -> Vec<PathBuf> {
+    let mut out = Vec::new();
+    match std::env::var_os("XDG_DATA_HOME").filter(|v| !v.is_empty()) {
+        Some(home) => out.push(PathBuf::from(home)),
+        None => out.extend(dirs::home_dir().map(|h| h.join(".local/share"))),
+    }
+    let system = std::env::var("XDG_DATA_DIRS")
+        .ok()
+        .filter(|v| !v.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".to_string());
+    out.extend(
+        system
+            .split(':')
+            .filter(|d| !d.is_empty())
+            .map(PathBuf::from),
+    );
+
+    let exports = [
+        dirs::data_dir().map(|d| d.join("flatpak/exports/share")),
+        Some(PathBuf::from("/var/lib/flatpak/exports/share")),
+        Some(PathBuf::from("/var/lib/snapd/desktop")),
+    ];
+    for dir in exports.into_iter().flatten() {
+        if !out.contains(&dir) {
+            out.push(dir);
+        }
+    }
+    out
+}
+
+fn collect_desktop_files(dir: &Path, out: &mut Vec<PathBuf>) {
+    let Ok(read) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in read.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_desktop_files(&path, out);
+        } else if path.extension().is_some_and(|e| e == "desktop") {
+            out.push(path);
+        }
+    }
+}
+
+/// `applications/kde/foo.desktop` has the ID `kde-foo.desktop`.
+fn desktop_file_id(apps_dir: &Path, file: &Path) -> Option<String> {
+    let rel = file.strip_prefix(apps_dir).ok()?;
+    let parts: Vec<_> = rel.iter().map(|p| p.to_string_lossy()).collect();
+    Some(parts.join("-"))
+}
+
+/// Locale keys to try for localized values, most specific first, e.g. `de_DE@euro`,
+/// `de_DE`, `de@euro`, `de` for `LANG=de_DE.UTF-8@euro`.
+fn user_locales() -> Vec<String> {
+    let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .find_map(|var| std::env::var(var).ok().filter(|v| !v.is_empty()))
+        .unwrap_or_default();
+    let (rest, modifier) = match locale.split_once('@') {
+        Some((rest, m)) => (rest, Some(m)),
+        None => (locale.as_str(), None),
+    };
+    let rest = rest.split('.').next().unwrap_or_default();
+    let (lang, country) = match rest.split_once('_') {
+        Some((l, c)) => (l, Some(c)),
+        None => (rest, None),
+    };
+    if lang.is_empty() || lang == "C" || lang == "POSIX" {
+        return Vec::new();
+    }
+
+    let mut out = Vec::new();
+    if let (Some(c), Some(m)) = (country, modifier) {
+        out.push(format!("{lang}_{c}@{m}"));
+    }
+    if let Some(c) = country {
+        out.push(format!("{lang}_{c}"));
+    }
+    if let Some(m) = modifier {
+        out.push(format!("{lang}@{m}"));
+    }
+    out.push(lang.to_string());
+    out
+}
+
+/// Undo the escapes allowed in string values: `\s`, `\n`, `\t`, `\r` and `\\`. Other
+/// backslashes are kept, e.g. for `\;` in lists.
+fn unescape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('s') => out.push(' '),
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some('r') => out.push('\r'),
+            Some('\\') => out.push('\\'),
+            Some(other) => {
+                out.push('\\');
+                out.push(other);
+            }
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+/// Split a `;`-separated list, honouring `\;`.
+fn split_list(value: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut cur = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.as_str().starts_with(';') => {
+                chars.next();
+                cur.push(';');
+            }
+            ';' => items.push(std::mem::take(&mut cur)),
+            _ => cur.push(c),
+        }
+    }
+    items.push(cur);
+    items.retain(|i| !i.is_empty());
+    items
+}
+
+/// Split an `Exec` value into arguments. Arguments may be double-quoted, and inside quotes
+/// `"`, `` ` ``, `$` and `\` are escaped with a backslash. `None` for unbalanced quotes.
+fn split_exec(exec: &str) -> Option<Vec<String>> {
+    let mut args = Vec::new();
+    let mut cur = String::new();
+    let mut in_arg = false;
+    let mut chars = exec.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                in_arg = true;
+                loop {
+                    match chars.next()? {
+                        '"' => break,
+                        '\\' => cur.push(chars.next()?),
+                        c => cur.push(c),
+                    }
+                }
+            }
+            ' ' | '\t' => {
+                if in_arg {
+                    args.push(std::mem::take(&mut cur));
+                    in_arg = false;
+                }
+            }
+            c => {
+                cur.push(c);
+                in_arg = true;
+            }
+        }
+    }
+    if in_arg {
+        args.push(cur);
+    }
+    Some(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(exec: &str) -> DesktopEntry {
+        let contents = format!("[Desktop Entry]\nType=Application\nName=Viewer\nExec={exec}\n");
+        DesktopEntry::parse(
+            Path::new("/usr/share/applications/viewer.desktop"),
+            &contents,
+            &[],
+        )
+        .unwrap()
+    }
+
+    fn command(exec: &str, file: &str) -> Vec<String> {
+        entry(exec).command_for(Path::new(file)).unwrap()
+    }
+
+    #[test]
+    fn parses_the_main_group_with_localized_names() {
+        let contents = "\
+[Desktop Entry]
+Type=Application
+Name=Image Viewer
+Name[de]=Bildbetrachter
+Exec=viewer %f
+MimeType=image/png;image/jpeg;
+NoDisplay=true
+
+[Desktop Action new-window]
+Name=New Window
+Exec=viewer --new-window
+";
+        let path = Path::new("/usr/share/applications/viewer.desktop");
+        let entry = DesktopEntry::parse(path, contents, &["de".to_string()]).unwrap();
+        assert_eq!(entry.name, "Bildbetrachter");
+        assert_eq!(entry.exec, "viewer %f");
+        assert_eq!(entry.mime_types, ["image/png", "image/jpeg"]);
+        assert!(entry.no_display);
+        assert!(!entry.hidden);
+
+        let english = DesktopEntry::parse(path, contents, &[]).unwrap();
+        assert_eq!(english.name, "Image Viewer");
+    }
+
+    #[test]
+    fn only_parses_applications() {
+        let contents = "[Desktop Entry]\nType=Link\nName=Site\nURL=https://example.com\n";
+        assert!(DesktopEntry::parse(Path::new("site.desktop"), contents, &[]).is_none());
+    }
+
+    #[test]
+    fn splits_quoted_exec_arguments() {
+        assert_eq!(
+            split_exec(r#"viewer "--title=My Viewer" "say \"hi\"" "\$HOME" %f"#).unwrap(),
+            ["viewer", "--title=My Viewer", r#"say "hi""#, "$HOME", "%f"]
+        );
+        assert_eq!(split_exec("  viewer   %f  ").unwrap(), ["viewer", "%f"]);
+        assert!(split_exec(r#"viewer "unclosed"#).is_none());
+    }
+
+    #[test]
+    fn expands_each_file_code_to_the_file() {
+        for code in ["%f", "%F", "%u", "%U"] {
+            assert_eq!(
+                command(&format!("viewer --open {code}"), "/tmp/shot.png"),
+                ["viewer", "--open", "/tmp/shot.png"],
+                "{code}"
+            );
+        }
+    }
+
+    #[test]
+    fn passes_the_file_once() {
+        assert_eq!(
+            command("viewer %f %U", "/tmp/shot.png"),
+            ["viewer", "/tmp/shot.png"]
+        );
+    }
+
+    #[test]
+    fn keeps_paths_with_spaces_as_one_argument() {
+        assert_eq!(
+            command("viewer %f", "/tmp/my shots/a.png"),
+            ["viewer", "/tmp/my shots/a.png"]
+        );
+    }
+
+    #[test]
+    fn expands_codes_inside_arguments() {
+        assert_eq!(
+            command(
+                r#"viewer "--file=%f" --name=%c --rate=100%%"#,
+                "/tmp/shot.png"
+            ),
+            [
+                "viewer",
+                "--file=/tmp/shot.png",
+                "--name=Viewer",
+                "--rate=100%"
+            ]
+        );
+    }
+
+    #[test]
+    fn appends_the_file_without_a_file_code() {
+        assert_eq!(
+            command("viewer --new %i", "/tmp/shot.png"),
+            ["viewer", "--new", "/tmp/shot.png"]
+        );
+    }
+
+    #[test]
+    fn adds_file_forwarding_to_flatpak_entries() {
+        assert_eq!(
+            command("/usr/bin/flatpak run org.gimp.GIMP %f", "/tmp/a b.png"),
+            [
+                "/usr/bin/flatpak",
+                "run",
+                "--file-forwarding",
+                "org.gimp.GIMP",
+                "@@",
+                "/tmp/a b.png",
+                "@@"
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_existing_file_forwarding() {
+        let exec = "/usr/bin/flatpak run --branch=stable --command=gimp --file-forwarding \
+                    org.gimp.GIMP @@u %U @@";
+        assert_eq!(
+            command(exec, "/tmp/shot.png"),
+            [
+                "/usr/bin/flatpak",
+                "run",
+                "--branch=stable",
+                "--command=gimp",
+                "--file-forwarding",
+                "org.gimp.GIMP",
+                "@@u",
+                "/tmp/shot.png",
+                "@@"
+            ]
+        );
+    }
+
+    #[test]
+    fn leaves_other_entries_unforwarded() {
+        let mut args = vec!["viewer".to_string(), "/tmp/shot.png".to_string()];
+        forward_flatpak_file(&mut args, "/tmp/shot.png");
+        assert_eq!(args, ["viewer", "/tmp/shot.png"]);
+    }
+}
@@ -0,0 +1,426 @@
+use crate::storage::ImageFormat;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// File types offered when saving a capture: label, MIME type, file patterns and format.
+const IMAGE_FILTERS: &[(&str, &str, &str, ImageFormat)] = &[
+    ("PNG image", "image/png", "*.png", ImageFormat::Png),
+    (
+        "JPEG image",
+        "image/jpeg",
+        "*.jpg *.jpeg",
+        ImageFormat::Jpeg,
+    ),
+];
+
+/// Ask the user for a folder, starting in `current` where the platform supports it.
+/// `Ok(None)` means the dialog was cancelled; an error means no folder picker could be
+/// shown at all.
+pub fn pick_folder(current: Option<&Path>) -> Result<Option<PathBuf>, String> {
+    #[cfg(target_os = "windows")]
+    {
+        let _ = current;
+        // Use PowerShell to show folder picker dialog
+        let script = r#"
+            Add-Type -AssemblyName System.Windows.Forms
+            $dialog = New-Object System.Windows.Forms.FolderBrowserDialog
+            $dialog.Description = "Select screenshot save location"
+            $dialog.ShowNewFolderButton = $true
+            if ($dialog.ShowDialog() -eq [System.Windows.Forms.DialogResult]::OK) {
+                Write-Output $dialog.SelectedPath
+            }
+        "#;
+
+        let output = Command::new("powershell")
+            .args(["-NoProfile", "-Command", script])
+            .output()
+            .map_err(|e| format!("Failed to show folder picker: {e}"))?;
+
+        Ok(picked_output(&output).map(PathBuf::from))
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        let _ = current;
+        let output = Command::new("osascript")
+            .args([
+                "-e",
+                "POSIX path of (choose folder with prompt \"Select screenshot save location\")",
+            ])
+            .output()
+            .map_err(|e| format!("Failed to show folder picker: {e}"))?;
+
+        Ok(picked_output(&output).map(PathBuf::from))
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        let portal_error =
+            match crate::portal::pick_folder("Select screenshot save location", current) {
+                Ok(path) => return Ok(path),
+                Err(e) => e,
+            };
+        log::warn!("Folder picker portal unavailable, trying zenity and kdialog: {portal_error}");
+
+        let start = current
+            .filter(|d| d.is_dir())
+            .map(|d| d.to_string_lossy().into_owned());
+        let mut zenity = Command::new("zenity");
+        zenity.args([
+            "--file-selection",
+            "--directory",
+            "--title=Select screenshot save location",
+        ]);
+        if let Some(start) = &start {
+            zenity.arg(format!("--filename={start}/"));
+        }
+        let mut kdialog = Command::new("kdialog");
+        kdialog.args(["--getexistingdirectory", start.as_deref().unwrap_or("~")]);
+
+        match run_fallbacks([zenity, kdialog]) {
+            Some(picked) => Ok(picked.map(PathBuf::from)),
+            None => Err(no_picker_error(&portal_error, true)),
+        }
+    }
+
+    #[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
+    {
+        let _ = current;
+        Err("No folder picker on this platform; type the path instead".into())
+    }
+}
+
+/// Ask where to save a capture, starting in `dir` with `name` (without extension) and
+/// `format` preselected. The format follows the extension the user typed, or the file type
+/// they picked if they typed none, in which case its extension is added. An extension of a
+/// format OpenCap can't write is an error.
+pub fn pick_save_path(
+    dir: &Path,
+    name: &str,
+    format: ImageFormat,
+) -> Result<Option<(PathBuf, ImageFormat)>, String> {
+    let file_name = format!("{name}.{}", format.extension());
+    let picked = pick_save_file(dir, &file_name, format)?;
+    picked
+        .map(|(path, chosen)| with_format(path, chosen.unwrap_or(format)))
+        .transpose()
+}
+
+fn with_format(path: PathBuf, fallback: ImageFormat) -> Result<(PathBuf, ImageFormat), String> {
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
+    match ext.as_deref() {
+        Some("png") => Ok((path, ImageFormat::Png)),
+        Some("jpg" | "jpeg") => Ok((path, ImageFormat::Jpeg)),
+        // Anything else made of letters names a file type; other dots are part of the name,
+        // e.g. a date
+        Some(ext) if ext.chars().all(|c| c.is_ascii_alphabetic()) => Err(format!(
+            "Can't save as .{ext}; use a .png or .jpg file name"
+        )),
+        _ => {
+            let mut with_ext = path.into_os_string();
+            with_ext.push(format!(".{}", fallback.extension()));
+            Ok((PathBuf::from(with_ext), fallback))
+        }
+    }
+}
+
+/// Show a save dialog. Returns the path and, where the dialog has a file type chooser,
+/// the format picked in it.
+fn pick_save_file(
+    dir: &Path,
+    file_name: &str,
+    format: ImageFormat,
+) -> Result<Option<(PathBuf, Option<ImageFormat>)>, String> {
+    let selected = IMAGE_FILTERS
+        .iter()
+        .position(|(_, _, _, f)| *f == format)
+        .unwrap_or(0);
+
+    #[cfg(target_os = "windows")]
+    {
+        // Paths go through the environment to avoid quoting them into the script. The
+        // filter list follows IMAGE_FILTERS, and FilterIndex counts from 1.
+        let script = r#"
+            [Console]::OutputEncoding = [System.Text.Encoding]::UTF8
+            Add-Type -AssemblyName System.Windows.Forms
+            $dialog = New-Object System.Windows.Forms.SaveFileDialog
+            $dialog.Title = "Save screenshot as"
+            $dialog.Filter = "PNG image (*.png)|*.png|JPEG image (*.jpg)|*.jpg;*.jpeg"
+            $dialog.FilterIndex = [int]$env:OPENCAP_FILTER
+            $dialog.InitialDirectory = $env:OPENCAP_DIR
+            $dialog.FileName = $env:OPENCAP_NAME
+            if ($dialog.ShowDialog() -eq [System.Windows.Forms.DialogResult]::OK) {
+                Write-Output "$($dialog.FilterIndex)|$($dialog.FileName)"
+            }
+        "#;
+
+        let output = Command::new("powershell")
+            .args(["-NoProfile", "-Command", script])
+            .env("OPENCAP_FILTER", (selected + 1).to_string())
+            .env("OPENCAP_DIR", dir)
+            .env("OPENCAP_NAME", file_name)
+            .output()
+            .map_err(|e| format!("Failed to show save dialog: {e}"))?;
+
+        Ok(picked_output(&output).and_then(|out| with_filter_index(&out)))
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        // The save dialog has no file type chooser, so the format is asked for first, and
+        // the name offered gets its extension. The lists follow IMAGE_FILTERS; cancelling
+        // either dialog makes osascript fail.
+        let script = r#"
+            set formats to {"PNG image", "JPEG image"}
+            set extensions to {"png", "jpg"}
+            set preselected to item ((system attribute "OPENCAP_FILTER") as integer) of formats
+            set picked to choose from list formats with prompt "Save screenshot as" default items {preselected}
+            if picked is false then error number -128
+            repeat with n from 1 to count of formats
+                if item n of formats is item 1 of picked then exit repeat
+            end repeat
+            set fileName to (system attribute "OPENCAP_STEM") & "." & item n of extensions
+            set savePath to POSIX path of (choose file name with prompt "Save screenshot as" default name fileName default location (POSIX file (system attribute "OPENCAP_DIR")))
+            return (n as text) & "|" & savePath
+        "#;
+        let stem = Path::new(file_name).file_stem().unwrap_or_default();
+        let output = Command::new("osascript")
+            .args(["-e", script])
+            .env("OPENCAP_FILTER", (selected + 1).to_string())
+            .env("OPENCAP_DIR", dir)
+            .env("OPENCAP_STEM", stem)
+            .output()
+            .map_err(|e| format!("Failed to show save dialog: {e}"))?;
+
+        Ok(picked_output(&output).and_then(|out| with_filter_index(&out)))
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        let filters: Vec<(&str, &str)> = IMAGE_FILTERS
+            .iter()
+            .map(|(label, mime, _, _)| (*label, *mime))
+            .collect();
+        let portal_error = match crate::portal::save_file(
+            "Save screenshot as",
+            dir,
+            file_name,
+            &filters,
+            selected,
+        ) {
+            Ok(picked) => {
+                return Ok(picked.map(|(path, chosen)| (path, chosen.map(|i| IMAGE_FILTERS[i].3))))
+            }
+            Err(e) => e,
+        };
+        log::warn!("Save dialog portal unavailable, trying zenity and kdialog: {portal_error}");
+
+        // Neither tool says which filter was picked, so the format comes from the extension.
+        // Both start on the first filter, so the preselected format goes first.
+        let mut ordered: Vec<_> = IMAGE_FILTERS.iter().collect();
+        ordered.swap(0, selected);
+        let start = dir.join(file_name).to_string_lossy().into_owned();
+        let mut zenity = Command::new("zenity");
+        zenity.args([
+            "--file-selection",
+            "--save",
+            "--confirm-overwrite",
+            "--title=Save screenshot as",
+            &format!("--filename={start}"),
+        ]);
+        zenity.args(
+            ordered
+                .iter()
+                .map(|(label, _, patterns, _)| format!("--file-filter={label} | {patterns}")),
+        );
+        let kdialog_filters: Vec<String> = ordered
+            .iter()
+            .map(|(label, _, patterns, _)| format!("{patterns}|{label}"))
+            .collect();
+        let mut kdialog = Command::new("kdialog");
+        kdialog.args(["--getsavefilename", &start, &kdialog_filters.join("\n")]);
+
+        match run_fallbacks([zenity, kdialog]) {
+            Some(picked) => Ok(picked.map(|path| (PathBuf::from(path), None))),
+            None => Err(no_picker_error(&portal_error, false)),
+        }
+    }
+
+    #[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
+    {
+        let _ = (dir, file_name, selected);
+        Err("No save dialog on this platform".into())
+    }
+}
+
+/// Ask for a settings file to import, or with `save` one to export to. Exported files get
+/// a `.json` extension if the user typed none.
+pub fn pick_settings_file(save: bool) -> Result<Option<PathBuf>, String> {
+    const FILE_NAME: &str = "opencap-settings.json";
+    let title = if save {
+        "Export settings"
+    } else {
+        "Import settings"
+    };
+
+    #[cfg(target_os = "windows")]
+    let picked = {
+        let _ = FILE_NAME;
+        let script = r#"
+            [Console]::OutputEncoding = [System.Text.Encoding]::UTF8
+            Add-Type -AssemblyName System.Windows.Forms
+            if ($env:OPENCAP_SAVE -eq "1") {
+                $dialog = New-Object System.Windows.Forms.SaveFileDialog
+                $dialog.FileName = "opencap-settings.json"
+            } else {
+                $dialog = New-Object System.Windows.Forms.OpenFileDialog
+            }
+            $dialog.Title = $env:OPENCAP_TITLE
+            $dialog.Filter = "OpenCap settings (*.json)|*.json|All files (*.*)|*.*"
+            if ($dialog.ShowDialog() -eq [System.Windows.Forms.DialogResult]::OK) {
+                Write-Output $dialog.FileName
+            }
+        "#;
+        let output = Command::new("powershell")
+            .args(["-NoProfile", "-Command", script])
+            .env("OPENCAP_SAVE", if save { "1" } else { "0" })
+            .env("OPENCAP_TITLE", title)
+            .output()
+            .map_err(|e| format!("Failed to show file dialog: {e}"))?;
+        picked_output(&output).map(PathBuf::from)
+    };
+
+    #[cfg(target_os = "macos")]
+    let picked = {
+        let script = if save {
+            format!(
+                r#"POSIX path of (choose file name with prompt "{title}" default name "{FILE_NAME}")"#
+            )
+        } else {
+            format!(
+                r#"POSIX path of (choose file with prompt "{title}" of type {{"public.json"}})"#
+            )
+        };
+        let output = Command::new("osascript")
+            .args(["-e", &script])
+            .output()
+            .map_err(|e| format!("Failed to show file dialog: {e}"))?;
+        picked_output(&output).map(PathBuf::from)
+    };
+
+    #[cfg(target_os = "linux")]
+    let picked = {
+        let filters = [("OpenCap settings", "application/json")];
+        let home = dirs::home_dir().unwrap_or_default();
+        let portal = if save {
+            crate::portal::save_file(title, &home, FILE_NAME, &filters, 0)
+                .map(|picked| picked.map(|(path, _)| path))
+        } else {
+            crate::portal::open_file(title, &filters)
+        };
+        match portal {
+            Ok(picked) => picked,
+            Err(portal_error) => {
+                log::warn!(
+                    "File dialog portal unavailable, trying zenity and kdialog: {portal_error}"
+                );
+                let start = home.join(FILE_NAME).to_string_lossy().into_owned();
+                let mut zenity = Command::new("zenity");
+                zenity.args([
+                    "--file-selection",
+                    &format!("--title={title}"),
+                    "--file-filter=OpenCap settings | *.json",
+                ]);
+                let mut kdialog = Command::new("kdialog");
+                if save {
+                    zenity.args([
+                        "--save",
+                        "--confirm-overwrite",
+                        &format!("--filename={start}"),
+                    ]);
+                    kdialog.args(["--getsavefilename", &start, "*.json|OpenCap settings"]);
+                } else {
+                    kdialog.args([
+                        "--getopenfilename",
+                        &home.to_string_lossy(),
+                        "*.json|OpenCap settings",
+                    ]);
+                }
+                match run_fallbacks([zenity, kdialog]) {
+                    Some(picked) => picked.map(PathBuf::from),
+                    None => return Err(no_picker_error(&portal_error, false)),
+                }
+            }
+        }
+    };
+
+    #[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
+    let picked: Option<PathBuf> = {
+        let _ = (FILE_NAME, title);
+        return Err("No file dialog on this platform".into());
+    };
+
+    Ok(picked.map(|path| {
+        if save && path.extension().is_none() {
+            path.with_extension("json")
+        } else {
+            path
+        }
+    }))
+}
+
+/// Run dialog tools in turn until one of them shows a dialog, and return what was picked.
+/// They exit with 1 when cancelled and with other codes when they can't show a dialog.
+/// `None` if none of them could.
+#[cfg(target_os = "linux")]
+fn run_fallbacks(commands: impl IntoIterator<Item = Command>) -> Option<Option<String>> {
+    for mut cmd in commands {
+        match cmd.output() {
+            Ok(output) if output.status.success() || output.status.code() == Some(1) => {
+                return Some(picked_output(&output));
+            }
+            Ok(output) => log::warn!("{:?} failed: {}", cmd.get_program(), output.status),
+            Err(_) => {}
+        }
+    }
+    None
+}
+
+/// `typeable` is for fields the path can be typed into instead.
+#[cfg(target_os = "linux")]
+fn no_picker_error(portal_error: &str, typeable: bool) -> String {
+    let instead = if typeable {
+        ", or type the path instead"
+    } else {
+        ""
+    };
+    format!(
+        "No file dialog available ({portal_error}). Install xdg-desktop-portal with a \
+         backend such as xdg-desktop-portal-gtk, or zenity or kdialog{instead}."
+    )
+}
+
+/// A save dialog's `index|path` output, with the file type picked counting from 1.
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+fn with_filter_index(out: &str) -> Option<(PathBuf, Option<ImageFormat>)> {
+    let (index, path) = out.split_once('|')?;
+    let chosen = index
+        .parse::<usize>()
+        .ok()
+        .and_then(|i| IMAGE_FILTERS.get(i.checked_sub(1)?))
+        .map(|(_, _, _, f)| *f);
+    Some((PathBuf::from(path), chosen))
+}
+
+/// What a dialog tool printed, or `None` if it was cancelled.
+#[cfg(any(target_os = "windows", target_os = "macos", target_os = "linux"))]
+fn picked_output(output: &std::process::Output) -> Option<String> {
+    if !output.status.success() {
+        return None;
+    }
+    let path = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (!path.is_empty()).then_some(path)
+}
@@ -0,0 +1,136 @@
+use image::{Rgba, RgbaImage};
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct PixelColor {
+    pub x: i32,
+    pub y: i32,
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+    pub hex: String,
+    pub rgb: String,
+    pub hsl: String,
+    pub oklch: String,
+}
+
+impl PixelColor {
+    /// Return the representation requested by name ("hex", "rgb", "hsl" or "oklch").
+    pub fn format(&self, name: &str) -> Option<&str> {
+        match name {
+            "hex" => Some(&self.hex),
+            "rgb" => Some(&self.rgb),
+            "hsl" => Some(&self.hsl),
+            "oklch" => Some(&self.oklch),
+            _ => None,
+        }
+    }
+}
+
+/// Sample the pixel at composite-relative coordinates `(cx, cy)`.
+/// `x`/`y` are the virtual-desktop coordinates reported back to the caller.
+pub fn sample_at(img: &RgbaImage, cx: i64, cy: i64, x: i32, y: i32) -> Result<PixelColor, String> {
+    if cx < 0 || cy < 0 || cx >= img.width() as i64 || cy >= img.height() as i64 {
+        return Err("Point is outside the captured desktop".into());
+    }
+    let Rgba([r, g, b, a]) = *img.get_pixel(cx as u32, cy as u32);
+
+    let (h, s, l) = rgb_to_hsl(r, g, b);
+    let (ok_l, ok_c, ok_h) = rgb_to_oklch(r, g, b);
+
+    Ok(PixelColor {
+        x,
+        y,
+        r,
+        g,
+        b,
+        a,
+        hex: format!("#{r:02X}{g:02X}{b:02X}"),
+        rgb: format!("rgb({r}, {g}, {b})"),
+        hsl: format!("hsl({h:.0}, {:.0}%, {:.0}%)", s * 100.0, l * 100.0),
+        oklch: format!("oklch({:.1}% {ok_c:.3} {ok_h:.1})", ok_l * 100.0),
+    })
+}
+
+/// Build a magnified crop centred on `(cx, cy)` for the overlay loupe.
+/// Each source pixel becomes a `zoom`×`zoom` block; pixels outside the image are transparent.
+pub fn loupe(
+    img: &RgbaImage,
+    cx: i64,
+    cy: i64,
+    radius: u32,
+    zoom: u32,
+) -> Result<RgbaImage, String> {
+    if zoom == 0 {
+        return Err("Zoom must be at least 1".into());
+    }
+    let span = radius * 2 + 1;
+    let size = span
+        .checked_mul(zoom)
+        .filter(|s| *s <= 2048)
+        .ok_or("Loupe is too large")?;
+
+    let mut out = RgbaImage::new(size, size);
+    for (ox, oy, px) in out.enumerate_pixels_mut() {
+        let sx = cx - radius as i64 + (ox / zoom) as i64;
+        let sy = cy - radius as i64 + (oy / zoom) as i64;
+        if sx >= 0 && sy >= 0 && sx < img.width() as i64 && sy < img.height() as i64 {
+            *px = *img.get_pixel(sx as u32, sy as u32);
+        }
+    }
+    Ok(out)
+}
+
+fn rgb_to_hsl(r: u8, g: u8, b: u8) -> (f32, f32, f32) {
+    let r = r as f32 / 255.0;
+    let g = g as f32 / 255.0;
+    let b = b as f32 / 255.0;
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let l = (max + min) / 2.0;
+    let d = max - min;
+    if d == 0.0 {
+        return (0.0, 0.0, l);
+    }
+
+    let s = d / (1.0 - (2.0 * l - 1.0).abs());
+    let h = if max == r {
+        60.0 * ((g - b) / d).rem_euclid(6.0)
+    } else if max == g {
+        60.0 * ((b - r) / d + 2.0)
+    } else {
+        60.0 * ((r - g) / d + 4.0)
+    };
+    (h, s, l)
+}
+
+fn srgb_to_linear(c: u8) -> f32 {
+    let c = c as f32 / 255.0;
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Convert sRGB to OKLCH (lightness 0..1, chroma, hue in degrees).
+fn rgb_to_oklch(r: u8, g: u8, b: u8) -> (f32, f32, f32) {
+    let (r, g, b) = (srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b));
+
+    let l = (0.412_221_46 * r + 0.536_332_55 * g + 0.051_445_995 * b).cbrt();
+    let m = (0.211_903_5 * r + 0.680_699_5 * g + 0.107_396_96 * b).cbrt();
+    let s = (0.088_302_46 * r + 0.281_718_85 * g + 0.629_978_7 * b).cbrt();
+
+    let ok_l = 0.210_454_26 * l + 0.793_617_8 * m - 0.004_072_047 * s;
+    let ok_a = 1.977_998_5 * l - 2.428_592_2 * m + 0.450_593_7 * s;
+    let ok_b = 0.025_904_037 * l + 0.782_771_77 * m - 0.808_675_77 * s;
+
+    let c = (ok_a * ok_a + ok_b * ok_b).sqrt();
+    // Hue is meaningless for greys; report 0 rather than float noise
+    let h = if c < 1e-4 {
+        0.0
+    } else {
+        ok_b.atan2(ok_a).to_degrees().rem_euclid(360.0)
+    };
+    (ok_l, c, h)
+}
@@ -1,13 +1,32 @@
+mod active_app;
+mod beautify;
 mod capture;
+mod cli;
 mod clipboard;
 mod config;
+#[cfg(target_os = "linux")]
+mod desktop_entry;
+mod dialogs;
+mod inspect;
+mod live_config;
+mod measure;
+#[cfg(target_os = "linux")]
+mod portal;
 mod programs;
+mod retention;
 mod settings_lib;
+mod snap;
 mod storage;
+mod trim;
+mod window_list;
 
+pub use cli::run_cli;
 pub use settings_lib::run_settings;
 
 use image::RgbaImage;
+use config::ProfileChoice;
+use live_config::LiveConfig;
+use storage::{CaptureContext, CaptureMode};
 use std::sync::Mutex;
 use tauri::Manager;
 use tauri::WebviewWindowBuilder;
@@ -22,10 +41,29 @@ pub struct ScreenLayout {
     pub origin_y: i32,
     pub virtual_width: u32,
     pub virtual_height: u32,
+    /// Visible windows in desktop coordinates, for snapping; only used by the backend.
+    #[serde(skip)]
+    pub windows: Vec<measure::Rect>,
 }
 
 pub struct PendingScreenLayout(pub Mutex<Option<ScreenLayout>>);
 
+/// Profile the current capture will be delivered with.
+pub struct ActiveProfile(pub Mutex<ProfileChoice>);
+
+/// App that had focus before the overlay opened, if any setting needs it.
+pub struct ActiveApp(pub Option<String>);
+
+/// Thread watching the program a capture was opened in, which the app waits for before
+/// exiting so a failure can still be reported.
+pub struct LaunchWatch(pub Mutex<Option<std::thread::JoinHandle<()>>>);
+
+#[derive(Clone, serde::Serialize)]
+pub struct ProfileList {
+    pub names: Vec<String>,
+    pub active: Option<String>,
+}
+
 #[tauri::command]
 fn get_pending_data_url(state: tauri::State<PendingDataUrl>) -> Result<String, String> {
     state.0.lock().unwrap().take().ok_or("No pending screenshot".into())
@@ -41,39 +79,195 @@ fn get_screen_layout(state: tauri::State<PendingScreenLayout>) -> Result<ScreenL
         .ok_or("No screen layout".into())
 }
 
+#[tauri::command]
+fn get_profiles(state: tauri::State<ActiveProfile>, live: tauri::State<LiveConfig>) -> ProfileList {
+    let cfg = live.current();
+    ProfileList {
+        names: cfg.profiles.iter().map(|p| p.name.clone()).collect(),
+        active: cfg.profile_name(&state.0.lock().unwrap()),
+    }
+}
+
+#[tauri::command]
+fn set_active_profile(
+    state: tauri::State<ActiveProfile>,
+    live: tauri::State<LiveConfig>,
+    name: Option<String>,
+) -> Result<(), String> {
+    // No name picks the base settings, not the default profile
+    let choice = match name {
+        Some(name) if live.current().find_profile(&name).is_none() => {
+            return Err(format!("Unknown profile: {name}"));
+        }
+        Some(name) => ProfileChoice::Named(name),
+        None => ProfileChoice::Base,
+    };
+    *state.0.lock().unwrap() = choice;
+    Ok(())
+}
+
+#[tauri::command]
+fn pick_color(
+    capture_state: tauri::State<PendingCapture>,
+    layout_state: tauri::State<PendingScreenLayout>,
+    x: i32,
+    y: i32,
+) -> Result<inspect::PixelColor, String> {
+    let (ox, oy) = layout_origin(&layout_state)?;
+    let guard = capture_state.0.lock().unwrap();
+    let img = guard.as_ref().ok_or("No pending capture")?;
+    inspect::sample_at(img, (x - ox) as i64, (y - oy) as i64, x, y)
+}
+
+#[tauri::command]
+fn get_loupe(
+    capture_state: tauri::State<PendingCapture>,
+    layout_state: tauri::State<PendingScreenLayout>,
+    x: i32,
+    y: i32,
+    radius: Option<u32>,
+    zoom: Option<u32>,
+) -> Result<String, String> {
+    let (ox, oy) = layout_origin(&layout_state)?;
+    let guard = capture_state.0.lock().unwrap();
+    let img = guard.as_ref().ok_or("No pending capture")?;
+    let crop = inspect::loupe(
+        img,
+        (x - ox) as i64,
+        (y - oy) as i64,
+        radius.unwrap_or(7),
+        zoom.unwrap_or(10),
+    )?;
+    capture::image_to_base64_png(&crop)
+}
+
+#[tauri::command]
+fn measure_distance(
+    layout_state: tauri::State<PendingScreenLayout>,
+    x1: i32,
+    y1: i32,
+    x2: i32,
+    y2: i32,
+) -> Result<measure::Distance, String> {
+    let guard = layout_state.0.lock().unwrap();
+    let layout = guard.as_ref().ok_or("No screen layout")?;
+    Ok(measure::distance(&layout.monitors, x1, y1, x2, y2))
+}
+
+#[tauri::command]
+fn find_element_bounds(
+    capture_state: tauri::State<PendingCapture>,
+    layout_state: tauri::State<PendingScreenLayout>,
+    x: i32,
+    y: i32,
+    tolerance: Option<u8>,
+) -> Result<measure::ElementBounds, String> {
+    let layout = layout_state
+        .0
+        .lock()
+        .unwrap()
+        .clone()
+        .ok_or("No screen layout")?;
+    let guard = capture_state.0.lock().unwrap();
+    let img = guard.as_ref().ok_or("No pending capture")?;
+
+    let cx = x - layout.origin_x;
+    let cy = y - layout.origin_y;
+    if cx < 0 || cy < 0 {
+        return Err("Point is outside the captured desktop".into());
+    }
+    let mut rect = measure::element_bounds(img, cx as u32, cy as u32, tolerance.unwrap_or(8))?;
+    rect.x += layout.origin_x;
+    rect.y += layout.origin_y;
+
+    let scale = measure::scale_at(&layout.monitors, x, y);
+    Ok(measure::ElementBounds {
+        rect,
+        physical_width: (rect.width as f32 * scale).round() as u32,
+        physical_height: (rect.height as f32 * scale).round() as u32,
+        scale_factor: scale,
+    })
+}
+
+/// Virtual-desktop origin of the pending composite, used to map desktop coordinates into it.
+fn layout_origin(state: &PendingScreenLayout) -> Result<(i32, i32), String> {
+    let guard = state.0.lock().unwrap();
+    let layout = guard.as_ref().ok_or("No screen layout")?;
+    Ok((layout.origin_x, layout.origin_y))
+}
+
+/// Suggest a snapped selection while dragging. Takes and returns the same
+/// composite-relative coordinates as `finish_region_capture`.
+#[tauri::command]
+fn snap_region(
+    capture_state: tauri::State<PendingCapture>,
+    layout_state: tauri::State<PendingScreenLayout>,
+    x: i32,
+    y: i32,
+    w: u32,
+    h: u32,
+    radius: Option<u32>,
+) -> Result<snap::SnapResult, String> {
+    let guides: Vec<measure::Rect> = {
+        let guard = layout_state.0.lock().unwrap();
+        let layout = guard.as_ref().ok_or("No screen layout")?;
+        let monitors = layout.monitors.iter().map(|m| measure::Rect {
+            x: m.x,
+            y: m.y,
+            width: m.width,
+            height: m.height,
+        });
+        monitors
+            .chain(layout.windows.iter().copied())
+            .map(|r| measure::Rect {
+                x: r.x - layout.origin_x,
+                y: r.y - layout.origin_y,
+                ..r
+            })
+            .collect()
+    };
+    let guard = capture_state.0.lock().unwrap();
+    let img = guard.as_ref().ok_or("No pending capture")?;
+    let rect = measure::Rect {
+        x,
+        y,
+        width: w,
+        height: h,
+    };
+    Ok(snap::snap_region(img, rect, &guides, radius.unwrap_or(8)))
+}
+
 #[tauri::command]
 fn finish_region_capture(
     app: tauri::AppHandle,
     state: tauri::State<PendingCapture>,
+    layout_state: tauri::State<PendingScreenLayout>,
+    profile_state: tauri::State<ActiveProfile>,
+    live: tauri::State<LiveConfig>,
     x: u32,
     y: u32,
     w: u32,
     h: u32,
+    save_as: Option<bool>,
 ) -> Result<(), String> {
     let img = state.0.lock().unwrap().take().ok_or("No pending capture")?;
     let cropped = capture::crop_region(&img, x, y, w, h)?;
-    let cfg = config::load_config();
-
-    let mut saved_path = None;
-    if cfg.save_locally {
-        let path = storage::save_screenshot(&cropped, cfg.save_path.as_deref())
-            .map_err(|e| format!("Save failed: {e}"))?;
-        saved_path = Some(path);
-    }
-
-    if cfg.copy_to_clipboard {
-        if let Err(e) = clipboard::copy_image_to_clipboard(&cropped) {
-            log::error!("Clipboard failed: {e}");
-        }
-    }
-
-    if cfg.auto_open {
-        if let Some(path) = &saved_path {
-            open_with_program(path, &cfg.open_with_program);
-        }
-    }
-
-    app.exit(0);
+    let profile = profile_state.0.lock().unwrap().clone();
+    let monitor = layout_state.0.lock().unwrap().as_ref().and_then(|layout| {
+        let cx = layout.origin_x + (x + w / 2) as i32;
+        let cy = layout.origin_y + (y + h / 2) as i32;
+        measure::monitor_at(&layout.monitors, cx, cy)
+    });
+    deliver_capture(
+        &app,
+        cropped,
+        &live.current(),
+        &profile,
+        (CaptureMode::Region, monitor.map(|i| i + 1)),
+        save_as.unwrap_or(false),
+    )?;
+
+    exit_after_launch(&app);
     Ok(())
 }
 
@@ -82,7 +276,10 @@ fn finish_monitor_capture(
     app: tauri::AppHandle,
     capture_state: tauri::State<PendingCapture>,
     layout_state: tauri::State<PendingScreenLayout>,
+    profile_state: tauri::State<ActiveProfile>,
+    live: tauri::State<LiveConfig>,
     monitor_index: usize,
+    save_as: Option<bool>,
 ) -> Result<(), String> {
     let img = capture_state
         .0
@@ -104,82 +301,329 @@ fn finish_monitor_capture(
     let x = (mon.x - layout.origin_x) as u32;
     let y = (mon.y - layout.origin_y) as u32;
     let cropped = capture::crop_region(&img, x, y, mon.width, mon.height)?;
-    let cfg = config::load_config();
+    let profile = profile_state.0.lock().unwrap().clone();
+    deliver_capture(
+        &app,
+        cropped,
+        &live.current(),
+        &profile,
+        (CaptureMode::Monitor, Some(monitor_index + 1)),
+        save_as.unwrap_or(false),
+    )?;
+
+    exit_after_launch(&app);
+    Ok(())
+}
 
+#[tauri::command]
+fn capture_full_and_finish(
+    app: tauri::AppHandle,
+    state: tauri::State<PendingCapture>,
+    profile_state: tauri::State<ActiveProfile>,
+    live: tauri::State<LiveConfig>,
+    save_as: Option<bool>,
+) -> Result<(), String> {
+    let img = state.0.lock().unwrap().take().ok_or("No pending capture")?;
+    let profile = profile_state.0.lock().unwrap().clone();
+    deliver_capture(
+        &app,
+        img,
+        &live.current(),
+        &profile,
+        (CaptureMode::Full, None),
+        save_as.unwrap_or(false),
+    )?;
+
+    exit_after_launch(&app);
+    Ok(())
+}
+
+/// Post-process a finished capture and run the configured save/clipboard/open actions.
+/// `source` is the capture mode and the monitor it's on, counting from 1. `save_as` asks
+/// where to save this capture even if the config doesn't.
+fn deliver_capture(
+    app: &tauri::AppHandle,
+    img: RgbaImage,
+    cfg: &config::Config,
+    profile: &ProfileChoice,
+    (mode, monitor): (CaptureMode, Option<usize>),
+    save_as: bool,
+) -> Result<(), String> {
+    let profile_name = cfg.profile_name(profile);
+    let cfg = cfg.with_profile(profile);
+
+    let img = if cfg.trim.enabled {
+        trim::trim_borders(&img, cfg.trim.tolerance, cfg.trim.padding)
+    } else {
+        img
+    };
+    let img = if cfg.beautify.enabled {
+        beautify::beautify(&img, &cfg.beautify.options)?
+    } else {
+        img
+    };
+
+    let focused_app = app.state::<ActiveApp>();
+    let context = CaptureContext {
+        mode,
+        profile: profile_name.as_deref(),
+        monitor,
+        app: focused_app.0.as_deref(),
+        now: chrono::Local::now(),
+        size: img.dimensions(),
+    };
+    let opts = storage::SaveOptions {
+        format: cfg.format,
+        jpeg_quality: cfg.jpeg_quality,
+        filename_template: &cfg.filename_template,
+        context: &context,
+    };
+    // A policy that turns saving off also rules out saving on request, and one that fixes
+    // the save folder keeps Save As inside it
+    let save_as = save_as && (cfg.save_locally || !config::is_policy_locked("saveLocally"));
+    let confine = config::is_policy_locked("savePath")
+        .then(|| storage::base_screenshot_dir(cfg.save_path.as_deref()))
+        .transpose()
+        .map_err(|e| format!("Save failed: {e}"))?;
     let mut saved_path = None;
-    if cfg.save_locally {
-        let path = storage::save_screenshot(&cropped, cfg.save_path.as_deref())
+    if save_as || cfg.save_locally {
+        if save_as || cfg.save_as_prompt {
+            let dir = if confine.is_some() {
+                storage::get_screenshot_dir(&cfg, &context)
+            } else {
+                storage::screenshot_dir(&cfg, &context)
+            }
             .map_err(|e| format!("Save failed: {e}"))?;
-        saved_path = Some(path);
+            // The overlay would cover the dialog
+            hide_windows(app);
+            saved_path = save_capture_as(&img, &dir, confine.as_deref(), &opts)
+                .map_err(|e| format!("Save failed: {e}"))?;
+        } else {
+            let dir = storage::get_screenshot_dir(&cfg, &context)
+                .map_err(|e| format!("Save failed: {e}"))?;
+            let path = storage::save_screenshot(&img, &dir, &opts)
+                .map_err(|e| format!("Save failed: {e}"))?;
+            saved_path = Some(path);
+        }
     }
 
     if cfg.copy_to_clipboard {
-        if let Err(e) = clipboard::copy_image_to_clipboard(&cropped) {
+        if let Err(e) = clipboard::copy_image_to_clipboard(&img) {
             log::error!("Clipboard failed: {e}");
         }
     }
 
+    if let Some(path) = &saved_path {
+        if let Err(e) = retention::record_capture(path) {
+            log::warn!("Failed to record {} for cleanup: {e}", path.display());
+        }
+        match retention::clean_up(&cfg, Some(path)) {
+            Ok(removed) => {
+                for expired in &removed {
+                    log::info!(
+                        "Moved {} to the trash: {}",
+                        expired.capture.path.display(),
+                        expired.reason
+                    );
+                }
+            }
+            Err(e) => log::warn!("Cleanup failed: {e}"),
+        }
+    }
+
     if cfg.auto_open {
         if let Some(path) = &saved_path {
-            open_with_program(path, &cfg.open_with_program);
+            // The app stays up while the launch is watched; don't leave the overlay up meanwhile
+            hide_windows(app);
+            if let Err(e) = open_with_program(app, path, &cfg.open_with, img.dimensions()) {
+                report_open_failure(app, path, &cfg.open_with.program, &e);
+            }
         }
     }
 
-    app.exit(0);
     Ok(())
 }
 
-#[tauri::command]
-fn capture_full_and_finish(
-    app: tauri::AppHandle,
-    state: tauri::State<PendingCapture>,
-) -> Result<(), String> {
-    let img = state.0.lock().unwrap().take().ok_or("No pending capture")?;
-    let cfg = config::load_config();
+fn hide_windows(app: &tauri::AppHandle) {
+    for window in app.webview_windows().values() {
+        let _ = window.hide();
+    }
+}
 
-    let mut saved_path = None;
-    if cfg.save_locally {
-        let path = storage::save_screenshot(&img, cfg.save_path.as_deref())
-            .map_err(|e| format!("Save failed: {e}"))?;
-        saved_path = Some(path);
+/// Ask where to save the capture, offering its usual folder, name and format. With
+/// `confine`, a file outside that folder is refused. `None` if the user cancels; the
+/// capture is then only copied, if that's enabled.
+fn save_capture_as(
+    img: &RgbaImage,
+    dir: &std::path::Path,
+    confine: Option<&std::path::Path>,
+    opts: &storage::SaveOptions,
+) -> Result<Option<std::path::PathBuf>, String> {
+    let name = storage::expand_template(opts.filename_template, opts.context)?;
+    // The folder is only created when saving there without asking, so start the dialog in
+    // the closest part of it that exists
+    let dir = dir.ancestors().find(|d| d.is_dir()).unwrap_or(dir);
+    let Some((path, format)) = dialogs::pick_save_path(dir, &name, opts.format)? else {
+        log::info!("Save cancelled");
+        return Ok(None);
+    };
+    if let Some(base) = confine {
+        let folder = path.parent().and_then(|p| std::fs::canonicalize(p).ok());
+        let base_folder = std::fs::canonicalize(base).ok();
+        if !folder.zip(base_folder).is_some_and(|(f, b)| f.starts_with(b)) {
+            return Err(format!(
+                "{} is outside {}, the save folder set by your administrator",
+                path.display(),
+                base.display()
+            ));
+        }
     }
+    storage::write_image(img, &path, format, opts.jpeg_quality)?;
+    Ok(Some(path))
+}
 
-    if cfg.copy_to_clipboard {
-        if let Err(e) = clipboard::copy_image_to_clipboard(&img) {
-            log::error!("Clipboard failed: {e}");
+/// Tell the user about a failure that doesn't stop the capture itself. The overlay is
+/// gone by then, so this goes to a desktop notification as well as the log.
+fn report_error(app: &tauri::AppHandle, message: &str) {
+    use tauri_plugin_notification::NotificationExt;
+
+    log::error!("{message}");
+    if let Err(e) = app
+        .notification()
+        .builder()
+        .title("OpenCap")
+        .body(message)
+        .show()
+    {
+        log::error!("Failed to show notification: {e}");
+    }
+}
+
+/// How long a launched program is watched for an immediate failure, e.g. a bad argument.
+const EARLY_EXIT_WINDOW: std::time::Duration = std::time::Duration::from_millis(1500);
+
+fn is_default_program(program: &str) -> bool {
+    program == "default" || program.is_empty()
+}
+
+/// Tell the user a capture couldn't be opened, then fall back to the default viewer.
+fn report_open_failure(
+    app: &tauri::AppHandle,
+    path: &std::path::Path,
+    program: &str,
+    error: &str,
+) {
+    report_error(app, &format!("Couldn't open the capture: {error}"));
+    if !is_default_program(program) {
+        if let Err(e) = open::that(path) {
+            report_error(app, &format!("The default image viewer failed too: {e}"));
         }
     }
+}
 
-    if cfg.auto_open {
-        if let Some(path) = &saved_path {
-            open_with_program(path, &cfg.open_with_program);
+/// Exit now, or once the launch being watched has finished, without blocking the caller.
+fn exit_after_launch(app: &tauri::AppHandle) {
+    match app.state::<LaunchWatch>().0.lock().unwrap().take() {
+        Some(watch) => {
+            let app = app.clone();
+            std::thread::spawn(move || {
+                let _ = watch.join();
+                app.exit(0);
+            });
         }
+        None => app.exit(0),
     }
+}
 
-    app.exit(0);
+/// Spawn `cmd`, failing if it can't start, and watch it on another thread for
+/// `EARLY_EXIT_WINDOW`: an unsuccessful exit in that time is reported, and the capture
+/// opened in the default viewer instead. Programs still running after that are assumed
+/// to be fine.
+fn spawn_checked(
+    app: &tauri::AppHandle,
+    mut cmd: std::process::Command,
+    path: &std::path::Path,
+    program: &str,
+) -> Result<(), String> {
+    let name = cmd.get_program().to_string_lossy().into_owned();
+    let mut child = cmd
+        .spawn()
+        .map_err(|e| format!("failed to start {name}: {e}"))?;
+    let (app_handle, path, program) = (app.clone(), path.to_path_buf(), program.to_string());
+    let watch = std::thread::spawn(move || {
+        let started = std::time::Instant::now();
+        let failure = loop {
+            match child.try_wait() {
+                Ok(Some(status)) if status.success() => break None,
+                Ok(Some(status)) => break Some(format!("{name} exited with {status}")),
+                Ok(None) if started.elapsed() < EARLY_EXIT_WINDOW => {
+                    std::thread::sleep(std::time::Duration::from_millis(50))
+                }
+                Ok(None) => break None,
+                Err(e) => break Some(format!("failed to watch {name}: {e}")),
+            }
+        };
+        if let Some(e) = failure {
+            report_open_failure(&app_handle, &path, &program, &e);
+        }
+    });
+    *app.state::<LaunchWatch>().0.lock().unwrap() = Some(watch);
     Ok(())
 }
 
-fn open_with_program(path: &std::path::Path, program: &str) {
-    if program == "default" || program.is_empty() {
-        let _ = open::that(path);
-    } else {
-        #[cfg(target_os = "windows")]
-        {
-            let _ = std::process::Command::new(program).arg(path).spawn();
-        }
-        #[cfg(target_os = "macos")]
-        {
-            let _ = std::process::Command::new("open")
-                .args(["-a", program])
-                .arg(path)
-                .spawn();
+fn open_with_program(
+    app: &tauri::AppHandle,
+    path: &std::path::Path,
+    open_with: &config::OpenWith,
+    size: (u32, u32),
+) -> Result<(), String> {
+    let program = open_with.program.as_str();
+    if is_default_program(program) {
+        return open::that(path).map_err(|e| format!("default image viewer failed: {e}"));
+    }
+    let args = programs::expand_args(&open_with.args, path, size)?;
+
+    #[cfg(target_os = "windows")]
+    let mut cmd = {
+        let mut cmd = std::process::Command::new(program);
+        cmd.args(&args);
+        cmd
+    };
+    #[cfg(target_os = "macos")]
+    let mut cmd = {
+        // `open -a` only forwards extra arguments after `--args`, and apps that are already
+        // running ignore those; a file given as an operand reaches them as an open-document event
+        let mut cmd = std::process::Command::new("open");
+        cmd.args(["-a", program]);
+        if programs::passes_only_path(&open_with.args) {
+            cmd.arg(path);
+        } else {
+            cmd.arg("--args").args(&args);
         }
-        #[cfg(target_os = "linux")]
-        {
-            let _ = std::process::Command::new(program).arg(path).spawn();
+        cmd
+    };
+    #[cfg(target_os = "linux")]
+    let mut cmd = {
+        // Programs detected from .desktop files are launched per their Exec line
+        if program.ends_with(".desktop") {
+            programs::desktop_entry_command(std::path::Path::new(program), path)
+                .ok_or_else(|| format!("can't read desktop entry {program}"))?
+        } else {
+            let mut cmd = std::process::Command::new(program);
+            cmd.args(&args);
+            cmd
         }
+    };
+    #[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
+    let mut cmd: std::process::Command = {
+        let _ = args;
+        return Err("Opening captures with a program isn't supported on this platform".into());
+    };
+
+    if let Some(dir) = open_with.working_dir.as_deref().filter(|d| !d.is_empty()) {
+        cmd.current_dir(dir);
     }
+    spawn_checked(app, cmd, path, program)
 }
 
 #[tauri::command]
@@ -190,19 +634,48 @@ fn cancel_region_capture(app: tauri::AppHandle) -> Result<(), String> {
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    let args: Vec<String> = std::env::args().collect();
+    let requested = cli::flag_value(&args, "--profile");
+    let live = LiveConfig::load();
+    let mut profile = ProfileChoice::requested(requested);
+    let unknown_profile = requested
+        .filter(|_| live.current().profile_name(&profile).is_none())
+        .map(|name| name.to_string());
+    if unknown_profile.is_some() {
+        profile = ProfileChoice::Default;
+    }
+    // Looked up before any window of ours can take focus
+    let focused_app = live
+        .current()
+        .uses_active_app()
+        .then(active_app::frontmost_app)
+        .flatten();
+
     tauri::Builder::default()
+        .plugin(tauri_plugin_notification::init())
         .manage(PendingCapture(Mutex::new(None)))
         .manage(PendingDataUrl(Mutex::new(None)))
         .manage(PendingScreenLayout(Mutex::new(None)))
+        .manage(ActiveProfile(Mutex::new(profile)))
+        .manage(ActiveApp(focused_app))
+        .manage(LaunchWatch(Mutex::new(None)))
+        .manage(live)
         .invoke_handler(tauri::generate_handler![
             get_pending_data_url,
             get_screen_layout,
+            get_profiles,
+            set_active_profile,
+            pick_color,
+            get_loupe,
+            measure_distance,
+            find_element_bounds,
+            snap_region,
             finish_region_capture,
             finish_monitor_capture,
             capture_full_and_finish,
             cancel_region_capture,
         ])
-        .setup(|app| {
+        .setup(move |app| {
             if cfg!(debug_assertions) {
                 app.handle().plugin(
                     tauri_plugin_log::Builder::default()
@@ -211,6 +684,15 @@ pub fn run() {
                 )?;
             }
 
+            if let Some(name) = &unknown_profile {
+                log::warn!("Unknown profile \"{name}\", using default settings");
+            }
+
+            // Pick up config edits made while the overlay is open
+            if let Err(e) = live_config::watch(app.handle()) {
+                log::warn!("{e}");
+            }
+
             // Capture all screens before any window exists — guaranteed clean
             let composite = capture::capture_all_screens()?;
 
@@ -230,6 +712,8 @@ pub fn run() {
                 );
             }
             let data_url = capture::image_to_base64_png(&composite.image)?;
+            // Listed before the overlay exists, so it isn't one of them
+            let windows = window_list::visible_windows();
 
             let layout = ScreenLayout {
                 monitors: composite.monitors.clone(),
@@ -237,6 +721,7 @@ pub fn run() {
                 origin_y: composite.origin_y,
                 virtual_width: composite.virtual_width,
                 virtual_height: composite.virtual_height,
+                windows,
             };
 
             *app.state::<PendingCapture>().0.lock().unwrap() = Some(composite.image);
@@ -0,0 +1,108 @@
+use crate::config::{self, Config};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::Path;
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager};
+
+/// Files whose changes affect the effective config.
+const WATCHED_FILES: &[&str] = &["config.json", "defaults.json", "policy.json"];
+
+/// The config a running instance works from, kept in sync with the files on disk.
+pub struct LiveConfig {
+    current: Mutex<Config>,
+    watcher: Mutex<Option<RecommendedWatcher>>,
+}
+
+impl LiveConfig {
+    pub fn load() -> Self {
+        Self {
+            current: Mutex::new(config::load_config()),
+            watcher: Mutex::new(None),
+        }
+    }
+
+    pub fn current(&self) -> Config {
+        self.current.lock().unwrap().clone()
+    }
+
+    /// Re-read the config from disk. If the user's file doesn't parse cleanly or has invalid
+    /// values, the last good config stays in effect. Problems with this machine rather than
+    /// the values, such as a missing folder or program, are only logged, like at startup,
+    /// so they can't hold back unrelated edits.
+    pub fn reload(&self) {
+        if let Err(e) = check_user_file() {
+            log::warn!("Ignoring config change, keeping last good config: {e}");
+            return;
+        }
+        let loaded = config::load_config_report();
+        if let Err(errors) = config::validate_config(&loaded.config) {
+            let (environment, invalid): (Vec<_>, Vec<_>) =
+                errors.into_iter().partition(|e| e.environment);
+            if !invalid.is_empty() {
+                for e in &invalid {
+                    log::warn!("Ignoring config change, keeping last good config: {e}");
+                }
+                return;
+            }
+            for e in &environment {
+                log::warn!("{e}");
+            }
+        }
+        for warning in &loaded.warnings {
+            log::warn!("{warning}");
+        }
+        *self.current.lock().unwrap() = loaded.config;
+        log::info!("Config reloaded");
+    }
+}
+
+/// Check that the user's file parses without losing anything. Unlike loading, this never
+/// restores a backup, so a half-written file in an editor is left alone.
+fn check_user_file() -> Result<(), String> {
+    let path = config::get_config_path()?;
+    if !path.exists() {
+        return Ok(());
+    }
+    let contents =
+        std::fs::read_to_string(&path).map_err(|e| format!("Failed to read config: {e}"))?;
+    let loaded = config::parse_config(&contents)?;
+    if loaded.lossy {
+        return Err(loaded.warnings.join("; "));
+    }
+    Ok(())
+}
+
+fn is_watched(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .is_some_and(|n| WATCHED_FILES.contains(&n))
+}
+
+/// Start watching the config directories and reload `LiveConfig` whenever a config file
+/// changes. Directories are watched rather than files because saves replace the file.
+pub fn watch(app: &AppHandle) -> Result<(), String> {
+    let handle = app.clone();
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        match res {
+            Ok(event) if !event.kind.is_access() && event.paths.iter().any(|p| is_watched(p)) => {
+                handle.state::<LiveConfig>().reload();
+            }
+            Ok(_) => {}
+            Err(e) => log::warn!("Config watcher error: {e}"),
+        }
+    })
+    .map_err(|e| format!("Failed to start config watcher: {e}"))?;
+
+    let dir = config::get_config_dir()?;
+    watcher
+        .watch(&dir, RecursiveMode::NonRecursive)
+        .map_err(|e| format!("Failed to watch {}: {e}", dir.display()))?;
+    if let Some(dir) = config::get_system_config_dir().filter(|d| d.is_dir()) {
+        if let Err(e) = watcher.watch(&dir, RecursiveMode::NonRecursive) {
+            log::warn!("Failed to watch {}: {e}", dir.display());
+        }
+    }
+
+    *app.state::<LiveConfig>().watcher.lock().unwrap() = Some(watcher);
+    Ok(())
+}
@@ -6,6 +6,8 @@ fn main() {
 
     if args.iter().any(|arg| arg == "--settings") {
         app_lib::run_settings();
+    } else if let Some(code) = app_lib::run_cli(args.get(1..).unwrap_or_default()) {
+        std::process::exit(code);
     } else {
         app_lib::run();
     }
@@ -0,0 +1,144 @@
+use crate::capture::MonitorInfo;
+use image::{Rgba, RgbaImage};
+
+/// Share of a boundary row/column that must still match the seed colour for the
+/// element to keep growing. Below 1.0 so text and icons inside a panel don't stop it.
+const FILL_THRESHOLD: f32 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ElementBounds {
+    pub rect: Rect,
+    pub physical_width: u32,
+    pub physical_height: u32,
+    pub scale_factor: f32,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Distance {
+    pub dx: i32,
+    pub dy: i32,
+    pub length: f64,
+    pub physical_dx: f64,
+    pub physical_dy: f64,
+    pub physical_length: f64,
+    pub scale_factor: f32,
+}
+
+/// Scale factor of the monitor containing the virtual-desktop point, or 1.0 in a gap.
+pub fn scale_at(monitors: &[MonitorInfo], x: i32, y: i32) -> f32 {
+    monitor_at(monitors, x, y)
+        .map(|i| monitors[i].scale_factor)
+        .unwrap_or(1.0)
+}
+
+/// Index of the monitor containing a virtual-desktop point.
+pub fn monitor_at(monitors: &[MonitorInfo], x: i32, y: i32) -> Option<usize> {
+    monitors
+        .iter()
+        .position(|m| x >= m.x && x < m.x + m.width as i32 && y >= m.y && y < m.y + m.height as i32)
+}
+
+/// Distance between two virtual-desktop points. Physical pixels use the scale of the
+/// monitor under the first point, since a ruler spanning mixed-DPI screens has no single answer.
+pub fn distance(monitors: &[MonitorInfo], x1: i32, y1: i32, x2: i32, y2: i32) -> Distance {
+    let scale = scale_at(monitors, x1, y1);
+    let dx = x2 - x1;
+    let dy = y2 - y1;
+    let physical_dx = dx as f64 * scale as f64;
+    let physical_dy = dy as f64 * scale as f64;
+
+    Distance {
+        dx,
+        dy,
+        length: (dx as f64).hypot(dy as f64),
+        physical_dx,
+        physical_dy,
+        physical_length: physical_dx.hypot(physical_dy),
+        scale_factor: scale,
+    }
+}
+
+/// Find the bounds of the UI element around `(cx, cy)` in composite coordinates.
+///
+/// Starts from the uniform-colour runs through the point, then pushes each side outward
+/// while the next row/column is mostly the same colour, stopping at borders and edges.
+pub fn element_bounds(img: &RgbaImage, cx: u32, cy: u32, tolerance: u8) -> Result<Rect, String> {
+    if cx >= img.width() || cy >= img.height() {
+        return Err("Point is outside the captured desktop".into());
+    }
+    let seed = *img.get_pixel(cx, cy);
+    let matches = |x: u32, y: u32| color_close(img.get_pixel(x, y), &seed, tolerance);
+
+    // Uniform-colour runs through the seed pixel
+    let mut left = cx;
+    while left > 0 && matches(left - 1, cy) {
+        left -= 1;
+    }
+    let mut right = cx;
+    while right + 1 < img.width() && matches(right + 1, cy) {
+        right += 1;
+    }
+    let mut top = cy;
+    while top > 0 && matches(cx, top - 1) {
+        top -= 1;
+    }
+    let mut bottom = cy;
+    while bottom + 1 < img.height() && matches(cx, bottom + 1) {
+        bottom += 1;
+    }
+
+    let column_fill = |x: u32, top: u32, bottom: u32| {
+        let hits = (top..=bottom).filter(|&y| matches(x, y)).count();
+        hits as f32 / (bottom - top + 1) as f32
+    };
+    let row_fill = |y: u32, left: u32, right: u32| {
+        let hits = (left..=right).filter(|&x| matches(x, y)).count();
+        hits as f32 / (right - left + 1) as f32
+    };
+
+    // Grow until no side can move; each pass can unblock the perpendicular sides
+    loop {
+        let mut grew = false;
+        while left > 0 && column_fill(left - 1, top, bottom) >= FILL_THRESHOLD {
+            left -= 1;
+            grew = true;
+        }
+        while right + 1 < img.width() && column_fill(right + 1, top, bottom) >= FILL_THRESHOLD {
+            right += 1;
+            grew = true;
+        }
+        while top > 0 && row_fill(top - 1, left, right) >= FILL_THRESHOLD {
+            top -= 1;
+            grew = true;
+        }
+        while bottom + 1 < img.height() && row_fill(bottom + 1, left, right) >= FILL_THRESHOLD {
+            bottom += 1;
+            grew = true;
+        }
+        if !grew {
+            break;
+        }
+    }
+
+    Ok(Rect {
+        x: left as i32,
+        y: top as i32,
+        width: right - left + 1,
+        height: bottom - top + 1,
+    })
+}
+
+pub fn color_close(a: &Rgba<u8>, b: &Rgba<u8>, tolerance: u8) -> bool {
+    a.0.iter()
+        .zip(b.0.iter())
+        .take(3)
+        .all(|(x, y)| x.abs_diff(*y) <= tolerance)
+}
@@ -0,0 +1,168 @@
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path::{Path, PathBuf};
+use zbus::blocking::{Connection, Proxy};
+use zbus::zvariant::{OwnedObjectPath, OwnedValue, Value};
+
+const PORTAL_SERVICE: &str = "org.freedesktop.portal.Desktop";
+const PORTAL_PATH: &str = "/org/freedesktop/portal/desktop";
+
+/// Ask the desktop for a folder through the XDG FileChooser portal, which works on any
+/// desktop running `xdg-desktop-portal` and from inside Flatpak. Returns `Ok(None)` if the
+/// user cancels and an error if there's no portal to ask.
+pub fn pick_folder(title: &str, current: Option<&Path>) -> Result<Option<PathBuf>, String> {
+    let mut options = HashMap::new();
+    options.insert("directory", Value::from(true));
+    if let Some(dir) = current.filter(|d| d.is_dir()) {
+        options.insert("current_folder", folder_value(dir));
+    }
+    let Some(mut results) = request("OpenFile", title, options)? else {
+        return Ok(None);
+    };
+    Ok(first_path(&mut results))
+}
+
+/// Ask for an existing file to open. `filters` are (label, MIME type) pairs.
+pub fn open_file(title: &str, filters: &[(&str, &str)]) -> Result<Option<PathBuf>, String> {
+    let mut options = HashMap::new();
+    options.insert("filters", Value::from(filter_values(filters)));
+    let Some(mut results) = request("OpenFile", title, options)? else {
+        return Ok(None);
+    };
+    Ok(first_path(&mut results))
+}
+
+/// Ask for a file to save to, starting in `folder` with `name` filled in. `filters` are
+/// (label, MIME type) pairs, with `current` selected first. Returns the path and the
+/// filter the user ended up with, if the portal says.
+pub fn save_file(
+    title: &str,
+    folder: &Path,
+    name: &str,
+    filters: &[(&str, &str)],
+    current: usize,
+) -> Result<Option<(PathBuf, Option<usize>)>, String> {
+    let values = filter_values(filters);
+    let mut options = HashMap::new();
+    options.insert("current_name", Value::from(name.to_string()));
+    if folder.is_dir() {
+        options.insert("current_folder", folder_value(folder));
+    }
+    if let Some(filter) = values.get(current) {
+        options.insert("current_filter", Value::from(filter.clone()));
+    }
+    options.insert("filters", Value::from(values));
+
+    let Some(mut results) = request("SaveFile", title, options)? else {
+        return Ok(None);
+    };
+    let chosen = results
+        .remove("current_filter")
+        .and_then(|v| <(String, Vec<(u32, String)>)>::try_from(v).ok())
+        .and_then(|(label, _)| filters.iter().position(|(l, _)| *l == label));
+    Ok(first_path(&mut results).map(|path| (path, chosen)))
+}
+
+/// Filters are a(sa(us)): a label and (kind, pattern) pairs, where kind 1 is a MIME type.
+fn filter_values(filters: &[(&str, &str)]) -> Vec<(String, Vec<(u32, String)>)> {
+    filters
+        .iter()
+        .map(|(label, mime)| (label.to_string(), vec![(1, mime.to_string())]))
+        .collect()
+}
+
+/// Call a FileChooser method and wait for the user's answer. `None` means cancelled.
+fn request(
+    method: &str,
+    title: &str,
+    mut options: HashMap<&str, Value<'_>>,
+) -> Result<Option<HashMap<String, OwnedValue>>, String> {
+    let conn = Connection::session().map_err(|e| format!("No D-Bus session: {e}"))?;
+    let chooser = Proxy::new(
+        &conn,
+        PORTAL_SERVICE,
+        PORTAL_PATH,
+        "org.freedesktop.portal.FileChooser",
+    )
+    .map_err(|e| format!("Failed to reach the desktop portal: {e}"))?;
+
+    // The answer arrives as a signal on a request object whose path is derived from our
+    // bus name and a token we pick. Subscribing before the call means it can't be missed.
+    let sender = conn
+        .unique_name()
+        .map(|n| n.as_str())
+        .ok_or("D-Bus connection has no name")?
+        .trim_start_matches(':')
+        .replace('.', "_");
+    let token = format!("opencap{}", std::process::id());
+    let request_path = format!("{PORTAL_PATH}/request/{sender}/{token}");
+    let request = Proxy::new(
+        &conn,
+        PORTAL_SERVICE,
+        request_path.as_str(),
+        "org.freedesktop.portal.Request",
+    )
+    .map_err(|e| format!("Failed to reach the desktop portal: {e}"))?;
+    let mut responses = request
+        .receive_signal("Response")
+        .map_err(|e| format!("Failed to listen for the portal's answer: {e}"))?;
+
+    options.insert("handle_token", Value::from(token));
+    let _: OwnedObjectPath = chooser
+        .call(method, &("", title, options))
+        .map_err(|e| format!("Desktop portal has no file chooser: {e}"))?;
+
+    let message = responses
+        .next()
+        .ok_or("Desktop portal closed without answering")?;
+    let (response, results): (u32, HashMap<String, OwnedValue>) = message
+        .body()
+        .deserialize()
+        .map_err(|e| format!("Unexpected answer from the desktop portal: {e}"))?;
+    // 0 is success, 1 is cancelled by the user, 2 is any other end of the dialog
+    match response {
+        0 => Ok(Some(results)),
+        1 => Ok(None),
+        _ => Err("Desktop portal file chooser failed".into()),
+    }
+}
+
+/// Byte string with a trailing NUL, as paths aren't necessarily UTF-8.
+fn folder_value(dir: &Path) -> Value<'static> {
+    let mut bytes = dir.as_os_str().as_bytes().to_vec();
+    bytes.push(0);
+    Value::from(bytes)
+}
+
+fn first_path(results: &mut HashMap<String, OwnedValue>) -> Option<PathBuf> {
+    let uris: Vec<String> = results
+        .remove("uris")
+        .and_then(|v| Vec::<String>::try_from(v).ok())
+        .unwrap_or_default();
+    uris.first().and_then(|uri| file_uri_to_path(uri))
+}
+
+/// Path of a `file://` URI, undoing percent-encoding.
+fn file_uri_to_path(uri: &str) -> Option<PathBuf> {
+    let encoded = uri.strip_prefix("file://")?.as_bytes();
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut i = 0;
+    while i < encoded.len() {
+        let hex = encoded
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (encoded[i], hex) {
+            (b'%', Some(byte)) => {
+                bytes.push(byte);
+                i += 3;
+            }
+            (b, _) => {
+                bytes.push(b);
+                i += 1;
+            }
+        }
+    }
+    Some(PathBuf::from(OsString::from_vec(bytes)))
+}
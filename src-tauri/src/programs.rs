@@ -1,10 +1,19 @@
+use crate::config::CustomProgram;
+#[cfg(target_os = "linux")]
+use crate::desktop_entry::{self, DesktopEntry, Packaging};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
 pub struct ImageProgram {
     pub name: String,
     pub path: String,
+    pub icon: Option<String>, // path to an icon file, where the platform provides one
+    pub args: String,         // argument template, see `expand_args`
+    pub working_dir: Option<String>,
+    pub custom: bool, // added by the user rather than detected
 }
 
 /// Detects installed image editing/viewing programs on the system
@@ -13,6 +22,7 @@ pub fn detect_image_programs() -> Vec<ImageProgram> {
         ImageProgram {
             name: "System Default".to_string(),
             path: "default".to_string(),
+            ..Default::default()
         },
     ];
 
@@ -34,37 +44,179 @@ pub fn detect_image_programs() -> Vec<ImageProgram> {
     programs
 }
 
+/// Detection results saved in the config dir, with the state of the directories detection
+/// looked at when they were written.
+#[derive(Serialize, Deserialize)]
+struct ProgramCache {
+    fingerprint: Vec<DirStamp>,
+    programs: Vec<ImageProgram>,
+}
+
+/// A directory and its modification time in milliseconds, `None` if it didn't exist.
+/// Installing or removing a program adds or removes an entry in one of them.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct DirStamp {
+    path: PathBuf,
+    modified: Option<u64>,
+}
+
+fn cache_path() -> Result<PathBuf, String> {
+    Ok(crate::config::get_config_dir()?.join("programs-cache.json"))
+}
+
+/// Detected programs, reusing the last results while none of the directories detection
+/// depends on have changed.
+pub fn cached_image_programs() -> Vec<ImageProgram> {
+    let fingerprint = detection_fingerprint();
+    let cached = cache_path()
+        .ok()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|contents| serde_json::from_str::<ProgramCache>(&contents).ok());
+    match cached {
+        Some(cache) if cache.fingerprint == fingerprint => cache.programs,
+        _ => detect_and_cache(fingerprint),
+    }
+}
+
+/// Run detection again regardless of the cache, e.g. after installing a program into a
+/// location the fingerprint doesn't cover.
+pub fn rescan_image_programs() -> Vec<ImageProgram> {
+    detect_and_cache(detection_fingerprint())
+}
+
+/// The fingerprint is taken before detecting, so a change made while detection runs
+/// causes another scan next time instead of being missed.
+fn detect_and_cache(fingerprint: Vec<DirStamp>) -> Vec<ImageProgram> {
+    let programs = detect_image_programs();
+    let cache = ProgramCache {
+        fingerprint,
+        programs,
+    };
+    let result = cache_path().and_then(|path| {
+        let json = serde_json::to_string_pretty(&cache)
+            .map_err(|e| format!("Failed to serialize program cache: {e}"))?;
+        crate::config::write_atomic(&path, json.as_bytes())
+    });
+    if let Err(e) = result {
+        log::warn!("Failed to write program cache: {e}");
+    }
+    cache.programs
+}
+
+fn detection_fingerprint() -> Vec<DirStamp> {
+    detection_dirs()
+        .into_iter()
+        .map(|path| {
+            let modified = std::fs::metadata(&path)
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                .map(|d| d.as_millis() as u64);
+            DirStamp { path, modified }
+        })
+        .collect()
+}
+
+/// Directories whose contents decide what detection finds.
+#[cfg(target_os = "linux")]
+fn detection_dirs() -> Vec<PathBuf> {
+    // `.desktop` files, including vendor subdirectories, and `PATH` for their `TryExec`
+    let mut out = Vec::new();
+    for apps in desktop_entry::data_dirs().iter().map(|d| d.join("applications")) {
+        collect_subdirs(&apps, &mut out);
+    }
+    if let Some(path) = std::env::var_os("PATH") {
+        out.extend(std::env::split_paths(&path));
+    }
+    out
+}
+
+#[cfg(target_os = "linux")]
+fn collect_subdirs(dir: &Path, out: &mut Vec<PathBuf>) {
+    out.push(dir.to_path_buf());
+    let Ok(read) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in read.flatten() {
+        if entry.file_type().is_ok_and(|t| t.is_dir()) {
+            collect_subdirs(&entry.path(), out);
+        }
+    }
+}
+
+/// For each candidate, the nearest existing directory on its path: the install directory
+/// once the program is there, otherwise the parent it would be created in.
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+fn detection_dirs() -> Vec<PathBuf> {
+    #[cfg(target_os = "windows")]
+    let candidates = WINDOWS_CANDIDATES;
+    #[cfg(target_os = "macos")]
+    let candidates = MACOS_CANDIDATES;
+
+    let mut out: Vec<PathBuf> = Vec::new();
+    for (_, pattern) in candidates {
+        // Wildcard paths are matched by listing the directory above the wildcard
+        let fixed = pattern.split('*').next().unwrap_or(pattern);
+        let mut dir = Path::new(fixed).parent();
+        while let Some(d) = dir.filter(|d| !d.is_dir()) {
+            dir = d.parent();
+        }
+        if let Some(d) = dir.filter(|d| !out.iter().any(|o| o == d)) {
+            out.push(d.to_path_buf());
+        }
+    }
+    out
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
+fn detection_dirs() -> Vec<PathBuf> {
+    Vec::new()
+}
+
+/// Common Windows image programs and their typical paths
+#[cfg(target_os = "windows")]
+const WINDOWS_CANDIDATES: &[(&str, &str)] = &[
+    ("Paint", r"C:\Windows\System32\mspaint.exe"),
+    ("Paint 3D", r"C:\Program Files\WindowsApps\Microsoft.MSPaint_*\PaintStudio.View.exe"),
+    ("Photos", r"C:\Program Files\WindowsApps\Microsoft.Windows.Photos_*\Microsoft.Photos.exe"),
+    ("GIMP", r"C:\Program Files\GIMP 2\bin\gimp-2.10.exe"),
+    ("GIMP", r"C:\Program Files\GIMP 2\bin\gimp-2.99.exe"),
+    ("Photoshop", r"C:\Program Files\Adobe\Adobe Photoshop 2024\Photoshop.exe"),
+    ("Photoshop", r"C:\Program Files\Adobe\Adobe Photoshop 2023\Photoshop.exe"),
+    ("Photoshop", r"C:\Program Files\Adobe\Adobe Photoshop CC 2019\Photoshop.exe"),
+    ("Krita", r"C:\Program Files\Krita (x64)\bin\krita.exe"),
+    ("IrfanView", r"C:\Program Files\IrfanView\i_view64.exe"),
+    ("IrfanView", r"C:\Program Files (x86)\IrfanView\i_view32.exe"),
+    ("XnView", r"C:\Program Files\XnView\xnview.exe"),
+    ("FastStone", r"C:\Program Files (x86)\FastStone Image Viewer\FSViewer.exe"),
+    ("Paint.NET", r"C:\Program Files\paint.net\paintdotnet.exe"),
+    ("Affinity Photo", r"C:\Program Files\Affinity\Photo 2\Photo.exe"),
+    ("Affinity Photo", r"C:\Program Files\Affinity\Photo\Photo.exe"),
+];
+
+#[cfg(target_os = "macos")]
+const MACOS_CANDIDATES: &[(&str, &str)] = &[
+    ("Preview", "/System/Applications/Preview.app"),
+    ("Photos", "/System/Applications/Photos.app"),
+    ("GIMP", "/Applications/GIMP-2.10.app"),
+    ("Photoshop", "/Applications/Adobe Photoshop 2024/Adobe Photoshop 2024.app"),
+    ("Krita", "/Applications/krita.app"),
+    ("Affinity Photo", "/Applications/Affinity Photo 2.app"),
+    ("Pixelmator Pro", "/Applications/Pixelmator Pro.app"),
+];
+
 #[cfg(target_os = "windows")]
 fn detect_windows_programs() -> Vec<ImageProgram> {
     let mut found = Vec::new();
 
-    // Common Windows image programs and their typical paths
-    let candidates = [
-        ("Paint", r"C:\Windows\System32\mspaint.exe"),
-        ("Paint 3D", r"C:\Program Files\WindowsApps\Microsoft.MSPaint_*\PaintStudio.View.exe"),
-        ("Photos", r"C:\Program Files\WindowsApps\Microsoft.Windows.Photos_*\Microsoft.Photos.exe"),
-        ("GIMP", r"C:\Program Files\GIMP 2\bin\gimp-2.10.exe"),
-        ("GIMP", r"C:\Program Files\GIMP 2\bin\gimp-2.99.exe"),
-        ("Photoshop", r"C:\Program Files\Adobe\Adobe Photoshop 2024\Photoshop.exe"),
-        ("Photoshop", r"C:\Program Files\Adobe\Adobe Photoshop 2023\Photoshop.exe"),
-        ("Photoshop", r"C:\Program Files\Adobe\Adobe Photoshop CC 2019\Photoshop.exe"),
-        ("Krita", r"C:\Program Files\Krita (x64)\bin\krita.exe"),
-        ("IrfanView", r"C:\Program Files\IrfanView\i_view64.exe"),
-        ("IrfanView", r"C:\Program Files (x86)\IrfanView\i_view32.exe"),
-        ("XnView", r"C:\Program Files\XnView\xnview.exe"),
-        ("FastStone", r"C:\Program Files (x86)\FastStone Image Viewer\FSViewer.exe"),
-        ("Paint.NET", r"C:\Program Files\paint.net\paintdotnet.exe"),
-        ("Affinity Photo", r"C:\Program Files\Affinity\Photo 2\Photo.exe"),
-        ("Affinity Photo", r"C:\Program Files\Affinity\Photo\Photo.exe"),
-    ];
-
-    for (name, path_pattern) in candidates {
+    for &(name, path_pattern) in WINDOWS_CANDIDATES {
         // Handle glob patterns for Windows Store apps
         if path_pattern.contains('*') {
             if let Some(resolved) = resolve_glob_path(path_pattern) {
                 found.push(ImageProgram {
                     name: name.to_string(),
                     path: resolved,
+                    ..Default::default()
                 });
             }
         } else {
@@ -75,6 +227,7 @@ fn detect_windows_programs() -> Vec<ImageProgram> {
                     found.push(ImageProgram {
                         name: name.to_string(),
                         path: path_pattern.to_string(),
+                        ..Default::default()
                     });
                 }
             }
@@ -117,21 +270,12 @@ fn resolve_glob_path(pattern: &str) -> Option<String> {
 fn detect_macos_programs() -> Vec<ImageProgram> {
     let mut found = Vec::new();
 
-    let candidates = [
-        ("Preview", "/System/Applications/Preview.app"),
-        ("Photos", "/System/Applications/Photos.app"),
-        ("GIMP", "/Applications/GIMP-2.10.app"),
-        ("Photoshop", "/Applications/Adobe Photoshop 2024/Adobe Photoshop 2024.app"),
-        ("Krita", "/Applications/krita.app"),
-        ("Affinity Photo", "/Applications/Affinity Photo 2.app"),
-        ("Pixelmator Pro", "/Applications/Pixelmator Pro.app"),
-    ];
-
-    for (name, path) in candidates {
+    for &(name, path) in MACOS_CANDIDATES {
         if PathBuf::from(path).exists() {
             found.push(ImageProgram {
                 name: name.to_string(),
                 path: path.to_string(),
+                ..Default::default()
             });
         }
     }
@@ -141,33 +285,332 @@ fn detect_macos_programs() -> Vec<ImageProgram> {
 
 #[cfg(target_os = "linux")]
 fn detect_linux_programs() -> Vec<ImageProgram> {
-    let mut found = Vec::new();
+    // Installed applications that declare they can open PNGs and aren't hidden from menus
+    let mut found: Vec<ImageProgram> = desktop_entry::installed_applications()
+        .into_iter()
+        .filter(|e| !e.no_display && !e.hidden)
+        .filter(|e| e.mime_types.iter().any(|m| m == "image/png"))
+        .filter(|e| {
+            e.try_exec
+                .as_deref()
+                .map_or(true, |t| resolve_program(t).is_some())
+        })
+        .map(|e| ImageProgram {
+            icon: e
+                .icon
+                .as_deref()
+                .and_then(desktop_entry::resolve_icon)
+                .map(|p| p.to_string_lossy().into_owned()),
+            // The same app can be installed natively and as a Flatpak or Snap
+            name: match e.packaging() {
+                Packaging::Native => e.name.clone(),
+                Packaging::Flatpak => format!("{} (Flatpak)", e.name),
+                Packaging::Snap => format!("{} (Snap)", e.name),
+            },
+            path: e.path.to_string_lossy().into_owned(),
+            ..Default::default()
+        })
+        .collect();
 
-    // Check common Linux image programs via `which`
-    let candidates = [
-        ("GIMP", "gimp"),
-        ("Krita", "krita"),
-        ("Inkscape", "inkscape"),
-        ("ImageMagick", "display"),
-        ("Eye of GNOME", "eog"),
-        ("Gwenview", "gwenview"),
-        ("feh", "feh"),
-        ("Shotwell", "shotwell"),
-    ];
+    found.sort_by_key(|p| p.name.to_lowercase());
+    found
+}
 
-    for (name, cmd) in candidates {
-        if let Ok(output) = std::process::Command::new("which").arg(cmd).output() {
-            if output.status.success() {
-                let path = String::from_utf8_lossy(&output.stdout).trim().to_string();
-                if !path.is_empty() {
-                    found.push(ImageProgram {
-                        name: name.to_string(),
-                        path,
-                    });
+/// Command that opens `file` with the application described by a `.desktop` file.
+#[cfg(target_os = "linux")]
+pub fn desktop_entry_command(entry: &Path, file: &Path) -> Option<std::process::Command> {
+    let args = DesktopEntry::load(entry)?.command_for(file)?;
+    let (program, args) = args.split_first()?;
+    let mut cmd = std::process::Command::new(program);
+    cmd.args(args);
+    Some(cmd)
+}
+
+/// Detected programs followed by the user's own, with one entry per program. Programs are
+/// the same if they resolve to the same executable; a user entry replaces a detected one,
+/// since it carries the user's name and arguments, and the first of several user entries
+/// for one program is kept.
+pub fn merge_programs(detected: Vec<ImageProgram>, custom: &[CustomProgram]) -> Vec<ImageProgram> {
+    let mut seen = HashSet::new();
+    let custom: Vec<ImageProgram> = custom
+        .iter()
+        // Entries that don't resolve are still the same if they name the same command
+        .filter(|c| {
+            seen.insert(program_identity(&c.command).unwrap_or_else(|| PathBuf::from(&c.command)))
+        })
+        .map(|c| ImageProgram {
+            name: c.name.clone(),
+            path: c.command.clone(),
+            args: c.args.clone(),
+            working_dir: c.working_dir.clone(),
+            custom: true,
+            ..Default::default()
+        })
+        .collect();
+
+    let mut merged: Vec<ImageProgram> = detected
+        .into_iter()
+        .filter(|p| program_identity(&p.path).map_or(true, |id| seen.insert(id)))
+        .collect();
+    merged.extend(custom);
+    merged
+}
+
+/// What makes two program entries the same program: the canonical executable, or for
+/// Flatpak and Snap apps (which share a launcher) the desktop entry itself.
+fn program_identity(program: &str) -> Option<PathBuf> {
+    if program == "default" {
+        return None;
+    }
+    #[cfg(target_os = "linux")]
+    if program.ends_with(".desktop") {
+        let entry = DesktopEntry::load(Path::new(program))?;
+        if entry.packaging() != Packaging::Native {
+            return Some(PathBuf::from(program));
+        }
+        return program_identity(&entry.executable()?);
+    }
+    let path = resolve_program(program)?;
+    Some(std::fs::canonicalize(&path).unwrap_or(path))
+}
+
+/// Find the executable (or on macOS, the app bundle) a configured program refers to.
+/// Bare names are looked up on `PATH`. Returns `None` if nothing runnable is found.
+pub fn resolve_program(program: &str) -> Option<PathBuf> {
+    let path = PathBuf::from(program);
+
+    #[cfg(target_os = "macos")]
+    {
+        // `open -a` takes a bundle path or an application name
+        if path.extension().is_some_and(|ext| ext == "app") && path.is_dir() {
+            return Some(path);
+        }
+        let home_apps = dirs::home_dir().map(|h| h.join("Applications"));
+        let app_dirs = [
+            Some(PathBuf::from("/Applications")),
+            Some(PathBuf::from("/System/Applications")),
+            home_apps,
+        ];
+        for dir in app_dirs.into_iter().flatten() {
+            let bundle = dir.join(format!("{program}.app"));
+            if bundle.is_dir() {
+                return Some(bundle);
+            }
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    if path.extension().is_some_and(|ext| ext == "desktop") {
+        let exe = DesktopEntry::load(&path)?.executable()?;
+        return resolve_program(&exe).map(|_| path);
+    }
+
+    if path.components().count() > 1 || path.is_absolute() {
+        return is_executable(&path).then_some(path);
+    }
+    let dirs = std::env::var_os("PATH")?;
+    std::env::split_paths(&dirs).find_map(|dir| find_in_dir(&dir, program))
+}
+
+#[cfg(target_os = "windows")]
+fn find_in_dir(dir: &Path, program: &str) -> Option<PathBuf> {
+    let candidate = dir.join(program);
+    if candidate.extension().is_some() && is_executable(&candidate) {
+        return Some(candidate);
+    }
+    let exts = std::env::var("PATHEXT").unwrap_or_else(|_| ".EXE;.BAT;.CMD;.COM".to_string());
+    exts.split(';')
+        .filter(|ext| !ext.is_empty())
+        .map(|ext| dir.join(format!("{program}{ext}")))
+        .find(|p| is_executable(p))
+}
+
+#[cfg(not(target_os = "windows"))]
+fn find_in_dir(dir: &Path, program: &str) -> Option<PathBuf> {
+    let candidate = dir.join(program);
+    is_executable(&candidate).then_some(candidate)
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// Arguments for opening `file`, from an `OpenWith::args` template. Arguments are split on
+/// whitespace, with single or double quotes grouping words; placeholders are expanded after
+/// splitting, so paths with spaces stay one argument. An empty template passes just the file;
+/// any other template must say where the file goes with `{path}`.
+pub fn expand_args(
+    template: &str,
+    file: &Path,
+    (width, height): (u32, u32),
+) -> Result<Vec<String>, String> {
+    let words = split_args(template)?;
+    if words.is_empty() {
+        return Ok(vec![file.to_string_lossy().into_owned()]);
+    }
+    let dir = file.parent().unwrap_or(Path::new(""));
+    let args = words
+        .iter()
+        .map(|word| {
+            let mut out = String::new();
+            let mut rest = word.as_str();
+            while let Some(start) = rest.find('{') {
+                out.push_str(&rest[..start]);
+                let end = rest[start..]
+                    .find('}')
+                    .ok_or_else(|| format!("Unclosed placeholder in arguments: {template}"))?;
+                let value = match &rest[start + 1..start + end] {
+                    "path" => file.to_string_lossy().into_owned(),
+                    "dir" => dir.to_string_lossy().into_owned(),
+                    "width" => width.to_string(),
+                    "height" => height.to_string(),
+                    name => return Err(format!("Unknown placeholder {{{name}}} in arguments")),
+                };
+                out.push_str(&value);
+                rest = &rest[start + end + 1..];
+            }
+            out.push_str(rest);
+            Ok(out)
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    if !words.iter().any(|word| word.contains("{path}")) {
+        return Err(format!(
+            "Arguments don't include {{path}}, so the capture wouldn't be opened: {template}"
+        ));
+    }
+    Ok(args)
+}
+
+/// Check an argument template without launching anything.
+pub fn check_args_template(template: &str) -> Result<(), String> {
+    expand_args(template, Path::new("capture.png"), (1, 1)).map(|_| ())
+}
+
+/// Whether a template passes nothing but the file, so it can be handed over the way the
+/// platform opens documents rather than as a command-line argument.
+pub fn passes_only_path(template: &str) -> bool {
+    split_args(template).is_ok_and(|words| words.is_empty() || words == ["{path}"])
+}
+
+/// Backslashes are not escapes, so Windows paths can be written as-is.
+fn split_args(template: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut cur = String::new();
+    let mut in_arg = false;
+    let mut quote = None;
+    for c in template.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => cur.push(c),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                in_arg = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_arg {
+                    args.push(std::mem::take(&mut cur));
+                    in_arg = false;
                 }
             }
+            (None, c) => {
+                cur.push(c);
+                in_arg = true;
+            }
         }
     }
+    if quote.is_some() {
+        return Err(format!("Unclosed quote in arguments: {template}"));
+    }
+    if in_arg {
+        args.push(cur);
+    }
+    Ok(args)
+}
 
-    found
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(template: &str, file: &str) -> Result<Vec<String>, String> {
+        expand_args(template, Path::new(file), (640, 480))
+    }
+
+    #[test]
+    fn empty_template_passes_just_the_file() {
+        assert_eq!(expand("", "/tmp/shot.png").unwrap(), ["/tmp/shot.png"]);
+        assert_eq!(expand("   ", "/tmp/shot.png").unwrap(), ["/tmp/shot.png"]);
+    }
+
+    #[test]
+    fn quotes_group_words() {
+        assert_eq!(
+            expand(r#"--title "My Shot" 'it''s' {path}"#, "/tmp/shot.png").unwrap(),
+            ["--title", "My Shot", "its", "/tmp/shot.png"]
+        );
+        assert_eq!(
+            expand(r#"--label="a 'b' c" {path}"#, "/tmp/shot.png").unwrap(),
+            ["--label=a 'b' c", "/tmp/shot.png"]
+        );
+    }
+
+    #[test]
+    fn keeps_backslashes_in_windows_paths() {
+        assert_eq!(
+            split_args(r"--out C:\Users\me {path}").unwrap(),
+            ["--out", r"C:\Users\me", "{path}"]
+        );
+    }
+
+    #[test]
+    fn paths_with_spaces_stay_one_argument() {
+        assert_eq!(
+            expand("--open {path} --in {dir}", "/tmp/my shots/a b.png").unwrap(),
+            ["--open", "/tmp/my shots/a b.png", "--in", "/tmp/my shots"]
+        );
+    }
+
+    #[test]
+    fn expands_placeholders_inside_words() {
+        assert_eq!(
+            expand("--size={width}x{height} --file={path}", "/tmp/shot.png").unwrap(),
+            ["--size=640x480", "--file=/tmp/shot.png"]
+        );
+    }
+
+    #[test]
+    fn requires_the_path_placeholder() {
+        let err = expand("--new-window {dir}", "/tmp/shot.png").unwrap_err();
+        assert!(err.contains("{path}"), "{err}");
+        assert!(check_args_template("--new-window").is_err());
+        assert!(check_args_template("--new-window {path}").is_ok());
+    }
+
+    #[test]
+    fn rejects_malformed_templates() {
+        assert!(expand("'{path}", "/tmp/shot.png")
+            .unwrap_err()
+            .starts_with("Unclosed quote"));
+        assert!(expand("{path", "/tmp/shot.png")
+            .unwrap_err()
+            .starts_with("Unclosed placeholder"));
+        assert!(expand("{path} {name}", "/tmp/shot.png")
+            .unwrap_err()
+            .starts_with("Unknown placeholder {name}"));
+    }
+
+    #[test]
+    fn recognises_templates_that_only_pass_the_file() {
+        assert!(passes_only_path(""));
+        assert!(passes_only_path(" \"{path}\" "));
+        assert!(!passes_only_path("--new {path}"));
+        assert!(!passes_only_path("{path}.png"));
+    }
 }
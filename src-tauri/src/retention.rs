@@ -0,0 +1,294 @@
+use crate::config::{Config, RetentionSettings};
+use crate::storage;
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Extensions of the files OpenCap saves; nothing else in the folders is touched.
+const CAPTURE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg"];
+
+/// Captures OpenCap has saved, next to `config.json`. The save folders can hold other
+/// images, such as the system's own screenshots, and only files listed here are removed.
+const MANIFEST_FILE: &str = "captures.json";
+
+/// A saved capture on disk.
+#[derive(Debug, Clone)]
+pub struct StoredCapture {
+    pub path: PathBuf,
+    pub size: u64,
+    pub modified: SystemTime,
+}
+
+/// A capture the retention policy removes, and the first limit it is over.
+#[derive(Debug, Clone)]
+pub struct Expired {
+    pub capture: StoredCapture,
+    pub reason: String,
+}
+
+/// Captures over the configured limits. `keep`, the capture just taken, counts towards
+/// them but is never removed, even if it alone exceeds them.
+pub fn expired_captures(cfg: &Config, keep: Option<&Path>) -> Result<Vec<Expired>, String> {
+    if !cfg.retention.is_enabled() {
+        return Ok(Vec::new());
+    }
+    let saved = read_manifest()?;
+    let mut captures = Vec::new();
+    let mut seen = HashSet::new();
+    for (dir, depth) in storage::managed_dirs(cfg)? {
+        collect_captures(&dir, depth, &mut seen, &mut captures);
+    }
+    captures.retain(|c| saved.contains(&canonical(&c.path)));
+    Ok(apply_policy(
+        &cfg.retention,
+        captures,
+        keep,
+        SystemTime::now(),
+    ))
+}
+
+/// Move captures over the configured limits to the trash. Returns what was removed.
+pub fn clean_up(cfg: &Config, keep: Option<&Path>) -> Result<Vec<Expired>, String> {
+    let expired = expired_captures(cfg, keep)?;
+    if !expired.is_empty() {
+        trash::delete_all(expired.iter().map(|e| &e.capture.path))
+            .map_err(|e| format!("Failed to move old captures to the trash: {e}"))?;
+    }
+    Ok(expired)
+}
+
+/// Remember a capture OpenCap just saved, so the retention policy may remove it later.
+/// Entries for files that are gone, e.g. already cleaned up, are dropped on the way.
+pub fn record_capture(path: &Path) -> Result<(), String> {
+    // A list that can't be read is left alone rather than replaced by this capture alone
+    let mut saved = read_manifest()?;
+    saved.retain(|p| p.exists());
+    saved.insert(canonical(path));
+    let mut paths: Vec<_> = saved.into_iter().collect();
+    paths.sort();
+    let json = serde_json::to_string_pretty(&paths)
+        .map_err(|e| format!("Failed to serialize capture list: {e}"))?;
+    crate::config::write_atomic(&manifest_path()?, json.as_bytes())
+}
+
+fn manifest_path() -> Result<PathBuf, String> {
+    Ok(crate::config::get_config_dir()?.join(MANIFEST_FILE))
+}
+
+/// Saved captures, or none if the list doesn't exist yet.
+fn read_manifest() -> Result<HashSet<PathBuf>, String> {
+    let path = manifest_path()?;
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(HashSet::new()),
+        Err(e) => return Err(format!("Failed to read {}: {e}", path.display())),
+    };
+    serde_json::from_str(&contents)
+        .map_err(|e| format!("{} is invalid ({e}); fix or delete it", path.display()))
+}
+
+/// How a capture is listed, so it matches however its folder was spelled.
+fn canonical(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Pick the captures to remove, newest kept first: anything too old, then whatever is
+/// left over the count and size limits.
+fn apply_policy(
+    policy: &RetentionSettings,
+    mut captures: Vec<StoredCapture>,
+    keep: Option<&Path>,
+    now: SystemTime,
+) -> Vec<Expired> {
+    captures.sort_by_key(|c| Reverse(c.modified));
+    let cutoff = policy
+        .max_age_days
+        .and_then(|days| now.checked_sub(Duration::from_secs(u64::from(days) * 24 * 60 * 60)));
+    let max_bytes = policy.max_total_mb.map(|mb| mb.saturating_mul(1024 * 1024));
+
+    let mut expired = Vec::new();
+    let mut kept_files = 0u64;
+    let mut kept_bytes = 0u64;
+    // Once a capture doesn't fit in the size limit, older ones go too, even smaller ones
+    let mut over_size = false;
+    let keep = keep.map(canonical);
+    for capture in captures {
+        if let Some(max) = max_bytes {
+            over_size = over_size || kept_bytes + capture.size > max;
+        }
+        let reason = if keep.as_ref() == Some(&canonical(&capture.path)) {
+            None
+        } else if cutoff.is_some_and(|cutoff| capture.modified < cutoff) {
+            policy.max_age_days.map(|d| format!("older than {d} days"))
+        } else if policy
+            .max_files
+            .is_some_and(|max| kept_files >= u64::from(max))
+        {
+            policy.max_files.map(|n| format!("more than {n} captures"))
+        } else if over_size {
+            policy.max_total_mb.map(|mb| format!("more than {mb} MB"))
+        } else {
+            None
+        };
+
+        match reason {
+            Some(reason) => expired.push(Expired { capture, reason }),
+            None => {
+                kept_files += 1;
+                kept_bytes += capture.size;
+            }
+        }
+    }
+    expired
+}
+
+/// Image files in `dir` and up to `depth` levels of subfolders below it.
+fn collect_captures(
+    dir: &Path,
+    depth: usize,
+    seen: &mut HashSet<PathBuf>,
+    out: &mut Vec<StoredCapture>,
+) {
+    let Ok(read) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in read.flatten() {
+        let path = entry.path();
+        // Symlinks aren't followed, so a link can't pull other folders into the policy
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if file_type.is_dir() {
+            if depth > 0 {
+                collect_captures(&path, depth - 1, seen, out);
+            }
+            continue;
+        }
+        let is_capture = file_type.is_file()
+            && path
+                .extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|e| CAPTURE_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()));
+        if !is_capture || !seen.insert(path.clone()) {
+            continue;
+        }
+        if let Ok(meta) = entry.metadata() {
+            out.push(StoredCapture {
+                path,
+                size: meta.len(),
+                modified: meta.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+    const MB: u64 = 1024 * 1024;
+
+    /// Captures named after their age in days, `size` MB each.
+    fn captures(now: SystemTime, ages: &[(u64, u64)]) -> Vec<StoredCapture> {
+        ages.iter()
+            .map(|&(days, size)| StoredCapture {
+                path: PathBuf::from(format!("{days}.png")),
+                size: size * MB,
+                modified: now - DAY * days as u32,
+            })
+            .collect()
+    }
+
+    fn removed(expired: &[Expired]) -> Vec<String> {
+        expired
+            .iter()
+            .map(|e| e.capture.path.display().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn removes_captures_older_than_max_age() {
+        let now = SystemTime::now();
+        let policy = RetentionSettings {
+            max_age_days: Some(7),
+            ..Default::default()
+        };
+        let expired = apply_policy(
+            &policy,
+            captures(now, &[(1, 1), (8, 1), (6, 1), (30, 1)]),
+            None,
+            now,
+        );
+        assert_eq!(removed(&expired), ["8.png", "30.png"]);
+        assert_eq!(expired[0].reason, "older than 7 days");
+    }
+
+    #[test]
+    fn keeps_the_newest_max_files() {
+        let now = SystemTime::now();
+        let policy = RetentionSettings {
+            max_files: Some(2),
+            ..Default::default()
+        };
+        let expired = apply_policy(
+            &policy,
+            captures(now, &[(3, 1), (1, 1), (4, 1), (2, 1)]),
+            None,
+            now,
+        );
+        assert_eq!(removed(&expired), ["3.png", "4.png"]);
+        assert_eq!(expired[0].reason, "more than 2 captures");
+    }
+
+    #[test]
+    fn older_captures_go_once_one_is_over_the_size_limit() {
+        let now = SystemTime::now();
+        let policy = RetentionSettings {
+            max_total_mb: Some(10),
+            ..Default::default()
+        };
+        // 4 + 5 fit; 3 doesn't, so the smaller 1 after it goes too
+        let expired = apply_policy(
+            &policy,
+            captures(now, &[(1, 4), (2, 5), (3, 3), (4, 1)]),
+            None,
+            now,
+        );
+        assert_eq!(removed(&expired), ["3.png", "4.png"]);
+        assert_eq!(expired[0].reason, "more than 10 MB");
+    }
+
+    #[test]
+    fn never_removes_the_kept_capture() {
+        let now = SystemTime::now();
+        let policy = RetentionSettings {
+            max_age_days: Some(1),
+            max_total_mb: Some(1),
+            max_files: Some(1),
+        };
+        // Even a kept capture that's over every limit on its own stays
+        let keep = PathBuf::from("5.png");
+        let expired = apply_policy(&policy, captures(now, &[(5, 20), (9, 1)]), Some(&keep), now);
+        assert_eq!(removed(&expired), ["9.png"]);
+    }
+
+    #[test]
+    fn keeps_the_capture_however_its_path_is_spelled() {
+        let now = SystemTime::now();
+        let dir = std::env::temp_dir().join(format!("opencap-retention-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::write(dir.join("5.png"), b"").unwrap();
+        let policy = RetentionSettings {
+            max_files: Some(0),
+            ..Default::default()
+        };
+        let mut captures = captures(now, &[(5, 1)]);
+        captures[0].path = dir.join("5.png");
+        let keep = dir.join("sub/../5.png");
+        let expired = apply_policy(&policy, captures, Some(&keep), now);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(expired.is_empty());
+    }
+}
@@ -1,106 +1,123 @@
-use crate::config::{self, Config};
+use crate::config::{self, Config, CustomProgram, FieldError, ImportPreview};
+use crate::dialogs;
 use crate::programs::{self, ImageProgram};
+use crate::storage;
+use std::sync::Mutex;
 use tauri::WebviewWindowBuilder;
 
+/// Warnings from the load behind the last `get_config`, so reporting them doesn't load the
+/// config (and copy aside a broken file) a second time.
+pub struct ConfigWarnings(Mutex<Vec<String>>);
+
+#[tauri::command]
+pub fn get_config(warnings: tauri::State<ConfigWarnings>) -> Config {
+    let loaded = config::load_config_report();
+    *warnings.0.lock().unwrap() = loaded.warnings;
+    loaded.config
+}
+
+/// Problems found while `get_config` loaded the config file, e.g. fields that were reset
+/// to defaults.
+#[tauri::command]
+pub fn get_config_warnings(warnings: tauri::State<ConfigWarnings>) -> Vec<String> {
+    warnings.0.lock().unwrap().clone()
+}
+
+/// Settings fixed by the machine policy or overridden for this run (dotted camelCase paths);
+/// the UI shows them read-only.
 #[tauri::command]
-pub fn get_config() -> Config {
-    config::load_config()
+pub fn get_locked_fields() -> Vec<String> {
+    config::locked_fields()
 }
 
+/// Validate and save. Errors are reported per field; problems not tied to one field use
+/// an empty `field`.
 #[tauri::command]
-pub fn save_config_cmd(config: Config) -> Result<(), String> {
-    // Validate: at least one output option must be enabled
-    if !config.copy_to_clipboard && !config.auto_open && !config.save_locally {
-        return Err("At least one option must be enabled (clipboard, auto-open, or save locally)".to_string());
+pub fn save_config_cmd(config: Config) -> Result<(), Vec<FieldError>> {
+    config::validate_config(&config)?;
+    let violations = config::policy_violations(&config);
+    if !violations.is_empty() {
+        return Err(violations
+            .into_iter()
+            .map(|field| FieldError::new(field, "Locked by policy or command-line override"))
+            .collect());
     }
-    config::save_config(&config)
+    config::save_config(&config).map_err(|e| vec![FieldError::new("", e)])
 }
 
+/// Write the current settings to a bundle file for sharing.
 #[tauri::command]
-pub fn get_image_programs() -> Vec<ImageProgram> {
-    programs::detect_image_programs()
+pub fn export_settings(path: String) -> Result<(), String> {
+    config::export_bundle(std::path::Path::new(&path))
 }
 
+/// Settings that importing the bundle at `path` would change, and anything to know first.
 #[tauri::command]
-pub fn browse_folder(_current_path: Option<String>) -> Option<String> {
-    use std::process::Command;
-
-    #[cfg(target_os = "windows")]
-    {
-        // Use PowerShell to show folder picker dialog
-        let script = r#"
-            Add-Type -AssemblyName System.Windows.Forms
-            $dialog = New-Object System.Windows.Forms.FolderBrowserDialog
-            $dialog.Description = "Select screenshot save location"
-            $dialog.ShowNewFolderButton = $true
-            if ($dialog.ShowDialog() -eq [System.Windows.Forms.DialogResult]::OK) {
-                Write-Output $dialog.SelectedPath
-            }
-        "#;
+pub fn preview_settings_import(path: String) -> Result<ImportPreview, String> {
+    let (imported, warnings) = config::read_bundle(std::path::Path::new(&path))?;
+    Ok(ImportPreview {
+        changes: config::diff_configs(&config::load_config(), &imported),
+        warnings,
+    })
+}
 
-        let output = Command::new("powershell")
-            .args(["-NoProfile", "-Command", script])
-            .output()
-            .ok()?;
+/// Ask for a settings file to import, or with `save` one to export to. `Ok(None)` means
+/// the dialog was cancelled.
+#[tauri::command]
+pub fn pick_settings_file(save: bool) -> Result<Option<String>, String> {
+    let picked = dialogs::pick_settings_file(save)?;
+    Ok(picked.map(|p| p.to_string_lossy().into_owned()))
+}
 
-        if output.status.success() {
-            let path = String::from_utf8_lossy(&output.stdout).trim().to_string();
-            if !path.is_empty() {
-                return Some(path);
-            }
-        }
-        None
-    }
+#[tauri::command]
+pub fn import_settings(path: String) -> Result<(), Vec<FieldError>> {
+    config::import_bundle(std::path::Path::new(&path))
+}
 
-    #[cfg(target_os = "macos")]
-    {
-        let output = Command::new("osascript")
-            .args(["-e", "POSIX path of (choose folder with prompt \"Select screenshot save location\")"])
-            .output()
-            .ok()?;
-
-        if output.status.success() {
-            let path = String::from_utf8_lossy(&output.stdout).trim().to_string();
-            if !path.is_empty() {
-                return Some(path);
-            }
-        }
-        None
-    }
+/// Detected programs merged with the user's own. The settings window passes the custom
+/// programs it is editing so unsaved changes show up; otherwise the saved ones are used.
+#[tauri::command]
+pub fn get_image_programs(custom: Option<Vec<CustomProgram>>) -> Vec<ImageProgram> {
+    let custom = custom.unwrap_or_else(|| config::load_config().custom_programs);
+    programs::merge_programs(programs::cached_image_programs(), &custom)
+}
 
-    #[cfg(target_os = "linux")]
-    {
-        // Try zenity first, then kdialog
-        let output = Command::new("zenity")
-            .args(["--file-selection", "--directory", "--title=Select screenshot save location"])
-            .output()
-            .or_else(|_| {
-                Command::new("kdialog")
-                    .args(["--getexistingdirectory", _current_path.as_deref().unwrap_or("~")])
-                    .output()
-            })
-            .ok()?;
-
-        if output.status.success() {
-            let path = String::from_utf8_lossy(&output.stdout).trim().to_string();
-            if !path.is_empty() {
-                return Some(path);
-            }
-        }
-        None
-    }
+/// Like `get_image_programs`, but detects programs again instead of using the cache.
+#[tauri::command]
+pub fn rescan_image_programs(custom: Option<Vec<CustomProgram>>) -> Vec<ImageProgram> {
+    let custom = custom.unwrap_or_else(|| config::load_config().custom_programs);
+    programs::merge_programs(programs::rescan_image_programs(), &custom)
+}
 
-    #[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
-    {
-        let _ = _current_path;
-        None
-    }
+/// The icon of a detected program as a data URL the settings window can show. Only icons
+/// detection found are read, and only formats a webview can draw.
+#[tauri::command]
+pub fn program_icon(program: String) -> Option<String> {
+    use base64::Engine;
+    let icon = programs::cached_image_programs()
+        .into_iter()
+        .find(|p| p.path == program)?
+        .icon?;
+    let mime = match std::path::Path::new(&icon).extension()?.to_str()? {
+        "png" => "image/png",
+        "svg" => "image/svg+xml",
+        _ => return None,
+    };
+    let bytes = std::fs::read(&icon).ok()?;
+    let b64 = base64::engine::general_purpose::STANDARD.encode(bytes);
+    Some(format!("data:{mime};base64,{b64}"))
+}
+
+/// Ask the user for a folder. `Ok(None)` means the dialog was cancelled.
+#[tauri::command]
+pub fn browse_folder(current_path: Option<String>) -> Result<Option<String>, String> {
+    let picked = dialogs::pick_folder(current_path.as_deref().map(std::path::Path::new))?;
+    Ok(picked.map(|p| p.to_string_lossy().into_owned()))
 }
 
 #[tauri::command]
 pub fn get_default_save_path() -> Result<String, String> {
-    let mut path = dirs::picture_dir().ok_or("Could not find Pictures directory")?;
-    path.push("Screenshots");
+    let path = storage::default_screenshot_dir()?;
     path.to_str()
         .map(|s| s.to_string())
         .ok_or("Invalid path".to_string())
@@ -108,10 +125,19 @@ pub fn get_default_save_path() -> Result<String, String> {
 
 pub fn run_settings() {
     tauri::Builder::default()
+        .manage(ConfigWarnings(Mutex::new(Vec::new())))
         .invoke_handler(tauri::generate_handler![
             get_config,
+            get_config_warnings,
+            get_locked_fields,
             save_config_cmd,
+            export_settings,
+            preview_settings_import,
+            import_settings,
+            pick_settings_file,
             get_image_programs,
+            rescan_image_programs,
+            program_icon,
             browse_folder,
             get_default_save_path,
         ])
@@ -126,7 +152,7 @@ pub fn run_settings() {
 
             WebviewWindowBuilder::new(app, "settings", tauri::WebviewUrl::App("settings.html".into()))
                 .title("OpenCap Settings")
-                .inner_size(450.0, 520.0)
+                .inner_size(450.0, 800.0)
                 .resizable(false)
                 .center()
                 .build()
@@ -0,0 +1,169 @@
+use crate::measure::Rect;
+use image::RgbaImage;
+
+/// Minimum per-channel difference between neighbouring pixels to count as an edge.
+const EDGE_THRESHOLD: u8 = 24;
+/// Share of a selection side that must lie on an edge before it snaps there.
+const MIN_COVERAGE: f32 = 0.6;
+/// Cap on pixels sampled per candidate line, keeping queries cheap on huge desktops.
+const MAX_SAMPLES: u32 = 256;
+/// Largest snap distance honoured, whatever the caller asks for.
+const MAX_RADIUS: u32 = 64;
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SnapResult {
+    pub rect: Rect,
+    pub left: bool,
+    pub top: bool,
+    pub right: bool,
+    pub bottom: bool,
+}
+
+/// Suggest a snapped version of `rect` (composite coordinates).
+///
+/// Each side moves to the strongest nearby edge within `radius` pixels (at most `MAX_RADIUS`),
+/// where an edge is either a colour transition running along most of that side or the border
+/// of one of `guides` (monitor or window rectangles). Work is bounded by `MAX_RADIUS` and
+/// `MAX_SAMPLES`, not image size, so this is safe to call on every mouse move.
+pub fn snap_region(img: &RgbaImage, rect: Rect, guides: &[Rect], radius: u32) -> SnapResult {
+    let radius = radius.min(MAX_RADIUS);
+    let (w, h) = (img.width() as i64, img.height() as i64);
+    let x0 = rect.x as i64;
+    let y0 = rect.y as i64;
+    let x1 = x0 + rect.width as i64;
+    let y1 = y0 + rect.height as i64;
+
+    let vertical_guides: Vec<i64> = guides
+        .iter()
+        .flat_map(|g| [g.x as i64, g.x as i64 + g.width as i64])
+        .collect();
+    let horizontal_guides: Vec<i64> = guides
+        .iter()
+        .flat_map(|g| [g.y as i64, g.y as i64 + g.height as i64])
+        .collect();
+
+    let span_y = (y0.max(0), y1.min(h));
+    let span_x = (x0.max(0), x1.min(w));
+    let left = best_edge(img, Axis::Vertical, x0, radius, span_y, &vertical_guides);
+    let right = best_edge(img, Axis::Vertical, x1, radius, span_y, &vertical_guides);
+    let top = best_edge(
+        img,
+        Axis::Horizontal,
+        y0,
+        radius,
+        span_x,
+        &horizontal_guides,
+    );
+    let bottom = best_edge(
+        img,
+        Axis::Horizontal,
+        y1,
+        radius,
+        span_x,
+        &horizontal_guides,
+    );
+
+    let (nx0, nx1) = match (left, right) {
+        (l, r) if l.unwrap_or(x0) < r.unwrap_or(x1) => (l.unwrap_or(x0), r.unwrap_or(x1)),
+        _ => (x0, x1),
+    };
+    let (ny0, ny1) = match (top, bottom) {
+        (t, b) if t.unwrap_or(y0) < b.unwrap_or(y1) => (t.unwrap_or(y0), b.unwrap_or(y1)),
+        _ => (y0, y1),
+    };
+
+    SnapResult {
+        rect: Rect {
+            x: nx0 as i32,
+            y: ny0 as i32,
+            width: (nx1 - nx0) as u32,
+            height: (ny1 - ny0) as u32,
+        },
+        left: nx0 != x0,
+        top: ny0 != y0,
+        right: nx1 != x1,
+        bottom: ny1 != y1,
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Axis {
+    /// A line of constant x, i.e. a left or right side
+    Vertical,
+    /// A line of constant y, i.e. a top or bottom side
+    Horizontal,
+}
+
+/// Pick the boundary position near `pos` with the best edge coverage over `span`.
+/// A boundary at `p` lies between pixel `p - 1` and pixel `p`.
+fn best_edge(
+    img: &RgbaImage,
+    axis: Axis,
+    pos: i64,
+    radius: u32,
+    span: (i64, i64),
+    guides: &[i64],
+) -> Option<i64> {
+    let limit = match axis {
+        Axis::Vertical => img.width() as i64,
+        Axis::Horizontal => img.height() as i64,
+    };
+    if span.1 <= span.0 {
+        return None;
+    }
+
+    let mut best: Option<(i64, f32)> = None;
+    let r = radius as i64;
+    for p in (pos - r).max(0)..=(pos + r).min(limit) {
+        let coverage = if guides.contains(&p) || p == 0 || p == limit {
+            1.0
+        } else {
+            edge_coverage(img, axis, p, span)
+        };
+        if coverage < MIN_COVERAGE {
+            continue;
+        }
+        let better = match best {
+            None => true,
+            Some((bp, bc)) => {
+                coverage > bc || (coverage == bc && (p - pos).abs() < (bp - pos).abs())
+            }
+        };
+        if better {
+            best = Some((p, coverage));
+        }
+    }
+    best.map(|(p, _)| p)
+}
+
+fn edge_coverage(img: &RgbaImage, axis: Axis, p: i64, span: (i64, i64)) -> f32 {
+    let len = (span.1 - span.0) as u32;
+    let step = len.div_ceil(MAX_SAMPLES).max(1);
+    let mut hits = 0u32;
+    let mut samples = 0u32;
+
+    for i in (0..len).step_by(step as usize) {
+        let along = (span.0 + i as i64) as u32;
+        let (a, b) = match axis {
+            Axis::Vertical => (
+                img.get_pixel(p as u32 - 1, along),
+                img.get_pixel(p as u32, along),
+            ),
+            Axis::Horizontal => (
+                img.get_pixel(along, p as u32 - 1),
+                img.get_pixel(along, p as u32),
+            ),
+        };
+        if a.0
+            .iter()
+            .zip(b.0.iter())
+            .take(3)
+            .any(|(x, y)| x.abs_diff(*y) > EDGE_THRESHOLD)
+        {
+            hits += 1;
+        }
+        samples += 1;
+    }
+
+    hits as f32 / samples as f32
+}
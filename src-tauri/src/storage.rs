@@ -1,28 +1,348 @@
+use crate::config::{self, Config, FolderRule};
+use chrono::{DateTime, Local};
 use image::RgbaImage;
-use std::path::PathBuf;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
 
-pub fn get_screenshot_dir(custom_path: Option<&str>) -> Result<PathBuf, String> {
-    let path = match custom_path {
-        Some(p) if !p.is_empty() => PathBuf::from(p),
-        _ => {
-            let mut path = dirs::picture_dir().ok_or("Could not find Pictures directory")?;
-            path.push("Screenshots");
-            path
+pub const DEFAULT_FILENAME_TEMPLATE: &str = "Screenshot_{date}_{time}";
+
+/// File format for saved captures.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageFormat {
+    #[default]
+    Png,
+    Jpeg,
+}
+
+impl ImageFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Jpeg => "jpg",
+        }
+    }
+}
+
+/// What part of the desktop a capture shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum CaptureMode {
+    Region,
+    Monitor,
+    Full,
+}
+
+impl CaptureMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            CaptureMode::Region => "region",
+            CaptureMode::Monitor => "monitor",
+            CaptureMode::Full => "full",
         }
+    }
+}
+
+/// Facts about a capture that templates and folder rules can refer to.
+pub struct CaptureContext<'a> {
+    pub mode: CaptureMode,
+    pub profile: Option<&'a str>,
+    /// Monitor the capture is on, counting from 1; a region is on the monitor under its
+    /// centre. `None` for captures of the whole desktop.
+    pub monitor: Option<usize>,
+    /// App that was active when the capture started, where the platform can tell.
+    pub app: Option<&'a str>,
+    pub now: DateTime<Local>,
+    /// Size of the final image.
+    pub size: (u32, u32),
+}
+
+impl CaptureContext<'_> {
+    /// Context for checking templates without a capture.
+    pub fn sample() -> CaptureContext<'static> {
+        CaptureContext {
+            mode: CaptureMode::Region,
+            profile: None,
+            monitor: Some(1),
+            app: None,
+            now: Local::now(),
+            size: (1, 1),
+        }
+    }
+}
+
+/// How a capture is written to disk.
+pub struct SaveOptions<'a> {
+    pub format: ImageFormat,
+    pub jpeg_quality: u8,
+    pub filename_template: &'a str,
+    pub context: &'a CaptureContext<'a>,
+}
+
+/// Where captures go when no save path is configured.
+pub fn default_screenshot_dir() -> Result<PathBuf, String> {
+    if let Some(dir) = config::portable_dir() {
+        return Ok(dir.join("Screenshots"));
+    }
+    let mut path = dirs::picture_dir().ok_or("Could not find Pictures directory")?;
+    path.push("Screenshots");
+    Ok(path)
+}
+
+/// The configured save folder, or the default one.
+pub fn base_screenshot_dir(custom_path: Option<&str>) -> Result<PathBuf, String> {
+    match custom_path {
+        Some(p) if !p.is_empty() => Ok(PathBuf::from(p)),
+        _ => default_screenshot_dir(),
+    }
+}
+
+/// Folder a capture is saved in: the folder of the first rule it matches, or the save
+/// folder, followed by the expanded subfolder template. It may not exist yet.
+pub fn screenshot_dir(cfg: &Config, ctx: &CaptureContext) -> Result<PathBuf, String> {
+    let base = base_screenshot_dir(cfg.save_path.as_deref())?;
+    let mut path = match cfg.folder_rules.iter().find(|r| rule_matches(r, ctx)) {
+        Some(rule) => {
+            // Rules are only validated when saved, not in a hand-edited file
+            let confine = config::is_policy_locked("savePath").then_some(base.as_path());
+            check_rule_folder(&rule.folder, confine)
+                .map_err(|e| format!("Folder rule \"{}\": {e}", rule.folder))?;
+            base.join(&rule.folder) // an absolute folder replaces the base
+        }
+        None => base,
     };
+    path.push(expand_subfolder(&cfg.subfolder_template, ctx)?);
+    Ok(path)
+}
 
+/// `screenshot_dir`, created if it doesn't exist.
+pub fn get_screenshot_dir(cfg: &Config, ctx: &CaptureContext) -> Result<PathBuf, String> {
+    let path = screenshot_dir(cfg, ctx)?;
     std::fs::create_dir_all(&path)
         .map_err(|e| format!("Failed to create screenshots dir: {e}"))?;
     Ok(path)
 }
 
-pub fn save_screenshot(img: &RgbaImage, custom_path: Option<&str>) -> Result<PathBuf, String> {
-    let dir = get_screenshot_dir(custom_path)?;
-    let timestamp = chrono::Local::now().format("%Y-%m-%d_%H-%M-%S");
-    let path = dir.join(format!("Screenshot_{timestamp}.png"));
+/// A rule's folder must be absolute or stay inside the save folder. `confine` is a save
+/// folder fixed by policy, which absolute folders must stay inside too.
+pub fn check_rule_folder(folder: &str, confine: Option<&Path>) -> Result<(), String> {
+    if folder.trim().is_empty() {
+        return Err("Folder is required".into());
+    }
+    let folder = Path::new(folder);
+    let climbs = folder
+        .components()
+        .any(|c| matches!(c, std::path::Component::ParentDir));
+    match confine {
+        Some(base) if climbs || (folder.is_absolute() && !folder.starts_with(base)) => {
+            Err(format!(
+                "Must be inside {}, the save folder set by your administrator",
+                base.display()
+            ))
+        }
+        None if folder.is_relative() && climbs => {
+            Err("Use an absolute path for folders outside the save folder".into())
+        }
+        _ => Ok(()),
+    }
+}
 
-    img.save(&path)
-        .map_err(|e| format!("Failed to save screenshot: {e}"))?;
+/// Folders `get_screenshot_dir` can put captures in, each with how many levels of
+/// subfolders below it captures can be in. Rule folders inside the save folder are
+/// covered by its depth.
+pub fn managed_dirs(cfg: &Config) -> Result<Vec<(PathBuf, usize)>, String> {
+    let template_depth = cfg
+        .subfolder_template
+        .split(['/', '\\'])
+        .filter(|p| !p.is_empty())
+        .count();
+    let mut base_depth = template_depth;
+    let mut dirs = Vec::new();
+    for rule in &cfg.folder_rules {
+        let folder = Path::new(&rule.folder);
+        if folder.is_absolute() {
+            dirs.push((folder.to_path_buf(), template_depth));
+        } else {
+            base_depth = base_depth.max(folder.components().count() + template_depth);
+        }
+    }
+    dirs.insert(0, (base_screenshot_dir(cfg.save_path.as_deref())?, base_depth));
+    Ok(dirs)
+}
+
+/// Whether a capture meets every condition the rule sets.
+fn rule_matches(rule: &FolderRule, ctx: &CaptureContext) -> bool {
+    rule.mode.map_or(true, |m| m == ctx.mode)
+        && rule
+            .profile
+            .as_deref()
+            .map_or(true, |p| ctx.profile == Some(p))
+        && rule.monitor.map_or(true, |m| ctx.monitor == Some(m))
+        && rule.app.as_deref().map_or(true, |a| {
+            ctx.app.is_some_and(|app| app.eq_ignore_ascii_case(a))
+        })
+}
+
+/// Expand a subfolder template such as `{year}/{month}` into a relative path. Each part
+/// is expanded like a file name, so placeholder values can't add levels or leave the
+/// folder. An empty template gives an empty path.
+pub fn expand_subfolder(template: &str, ctx: &CaptureContext) -> Result<PathBuf, String> {
+    let mut path = PathBuf::new();
+    for part in template.split(['/', '\\']).filter(|p| !p.is_empty()) {
+        let name = expand_template(part, ctx)?;
+        if name == "." || name == ".." {
+            return Err(format!("Subfolder template can't use \"{name}\""));
+        }
+        path.push(name);
+    }
+    Ok(path)
+}
+
+/// Check that captures could be saved to `custom_path` (or the default directory).
+pub fn check_screenshot_dir(custom_path: Option<&str>) -> Result<(), String> {
+    check_save_dir(&base_screenshot_dir(custom_path)?)
+}
+
+/// Check that captures could be saved to `path`: it must already be a writable directory,
+/// or be creatable inside one.
+pub fn check_save_dir(path: &Path) -> Result<(), String> {
+    if !path.is_absolute() {
+        return Err("Must be an absolute path".into());
+    }
+    if path.exists() && !path.is_dir() {
+        return Err(format!("{} is not a folder", path.display()));
+    }
+
+    // The folder itself if it exists, otherwise the closest parent it would be created in
+    let existing = path
+        .ancestors()
+        .find(|p| p.exists())
+        .ok_or_else(|| format!("{} can't be created", path.display()))?;
+    if !existing.is_dir() {
+        return Err(format!("{} is not a folder", existing.display()));
+    }
+    check_writable(existing)
+}
+
+/// Whether the folder's permissions let us create files in it. Validation runs on every
+/// config reload, so this only looks and never writes; saving reports anything it misses.
+fn check_writable(dir: &Path) -> Result<(), String> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        let c_path = std::ffi::CString::new(dir.as_os_str().as_bytes())
+            .map_err(|_| format!("{} is not a valid path", dir.display()))?;
+        // SAFETY: `c_path` is NUL-terminated and outlives the call
+        if unsafe { libc::access(c_path.as_ptr(), libc::W_OK) } != 0 {
+            let e = std::io::Error::last_os_error();
+            return Err(format!("{} is not writable: {e}", dir.display()));
+        }
+        Ok(())
+    }
 
+    #[cfg(not(unix))]
+    {
+        // Windows folders often carry the read-only attribute without it stopping writes,
+        // and their ACLs can't be judged without trying, so leave it to saving
+        let _ = dir;
+        Ok(())
+    }
+}
+
+/// Save `img` in `dir` under the next free name from the file name template.
+pub fn save_screenshot(img: &RgbaImage, dir: &Path, opts: &SaveOptions) -> Result<PathBuf, String> {
+    let stem = expand_template(opts.filename_template, opts.context)?;
+    let ext = opts.format.extension();
+
+    // Templates without seconds can repeat; never overwrite an earlier capture
+    let mut path = dir.join(format!("{stem}.{ext}"));
+    let mut n = 2;
+    while path.exists() {
+        path = dir.join(format!("{stem}_{n}.{ext}"));
+        n += 1;
+    }
+
+    write_image(img, &path, opts.format, opts.jpeg_quality)?;
     Ok(path)
 }
+
+/// Encode `img` to `path` in `format`, replacing any existing file.
+pub fn write_image(
+    img: &RgbaImage,
+    path: &Path,
+    format: ImageFormat,
+    jpeg_quality: u8,
+) -> Result<(), String> {
+    match format {
+        ImageFormat::Png => img
+            .save_with_format(path, image::ImageFormat::Png)
+            .map_err(|e| format!("Failed to save screenshot: {e}"))?,
+        ImageFormat::Jpeg => {
+            // JPEG has no alpha channel
+            let rgb = image::DynamicImage::ImageRgba8(img.clone()).to_rgb8();
+            let file = std::fs::File::create(path)
+                .map_err(|e| format!("Failed to save screenshot: {e}"))?;
+            let mut encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(
+                std::io::BufWriter::new(file),
+                jpeg_quality.clamp(1, 100),
+            );
+            encoder
+                .encode_image(&rgb)
+                .map_err(|e| format!("Failed to save screenshot: {e}"))?;
+        }
+    }
+    Ok(())
+}
+
+/// Expand `{placeholder}`s in a file name template. Path separators in the result are
+/// replaced so a template can't escape the screenshot directory.
+pub fn expand_template(template: &str, ctx: &CaptureContext) -> Result<String, String> {
+    let now = &ctx.now;
+    let (width, height) = ctx.size;
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| format!("Unclosed placeholder in template: {template}"))?;
+        let name = &rest[start + 1..start + end];
+        let value = match name {
+            "date" => now.format("%Y-%m-%d").to_string(),
+            "time" => now.format("%H-%M-%S").to_string(),
+            "year" => now.format("%Y").to_string(),
+            "month" => now.format("%m").to_string(),
+            "day" => now.format("%d").to_string(),
+            "hour" => now.format("%H").to_string(),
+            "minute" => now.format("%M").to_string(),
+            "second" => now.format("%S").to_string(),
+            "profile" => ctx.profile.unwrap_or("default").to_string(),
+            "width" => width.to_string(),
+            "height" => height.to_string(),
+            "mode" => ctx.mode.name().to_string(),
+            "monitor" => ctx.monitor.map_or("all".to_string(), |m| m.to_string()),
+            "app" => ctx.app.unwrap_or("unknown").to_string(),
+            _ => return Err(format!("Unknown placeholder {{{name}}} in template")),
+        };
+        out.push_str(&value);
+        rest = &rest[start + end + 1..];
+    }
+    out.push_str(rest);
+
+    let out = out.replace(['/', '\\'], "-");
+    if out.trim().is_empty() {
+        return Err("File name template produced an empty name".into());
+    }
+    Ok(out)
+}
+
+/// Check a file name template without saving anything.
+pub fn validate_template(template: &str) -> Result<(), String> {
+    expand_template(template, &CaptureContext::sample()).map(|_| ())
+}
+
+/// Check a subfolder template without creating anything.
+pub fn validate_subfolder_template(template: &str) -> Result<(), String> {
+    expand_subfolder(template, &CaptureContext::sample()).map(|_| ())
+}
@@ -0,0 +1,33 @@
+use crate::measure::color_close;
+use image::RgbaImage;
+
+/// Remove uniform margins from `img`, like ImageMagick's `-trim`.
+///
+/// The top-left pixel is taken as the background colour; rows and columns whose pixels are all
+/// within `tolerance` of it are stripped from each side. `padding` pixels of the original margin
+/// are kept around the content. Returns the image unchanged if it is entirely background.
+pub fn trim_borders(img: &RgbaImage, tolerance: u8, padding: u32) -> RgbaImage {
+    let (w, h) = img.dimensions();
+    if w == 0 || h == 0 {
+        return img.clone();
+    }
+    let bg = *img.get_pixel(0, 0);
+    let row_is_bg = |y: u32| (0..w).all(|x| color_close(img.get_pixel(x, y), &bg, tolerance));
+
+    let Some(top) = (0..h).find(|&y| !row_is_bg(y)) else {
+        return img.clone();
+    };
+    let bottom = (top..h).rev().find(|&y| !row_is_bg(y)).unwrap_or(top);
+
+    let col_is_bg =
+        |x: u32| (top..=bottom).all(|y| color_close(img.get_pixel(x, y), &bg, tolerance));
+    let left = (0..w).find(|&x| !col_is_bg(x)).unwrap_or(0);
+    let right = (left..w).rev().find(|&x| !col_is_bg(x)).unwrap_or(left);
+
+    let x0 = left.saturating_sub(padding);
+    let y0 = top.saturating_sub(padding);
+    let x1 = right.saturating_add(padding).min(w - 1);
+    let y1 = bottom.saturating_add(padding).min(h - 1);
+
+    image::imageops::crop_imm(img, x0, y0, x1 - x0 + 1, y1 - y0 + 1).to_image()
+}
@@ -0,0 +1,139 @@
+use crate::measure::Rect;
+
+/// Most windows looked at, topmost first; enough for any real desktop.
+const MAX_WINDOWS: usize = 64;
+
+/// Rectangles of visible top-level windows in desktop coordinates, for snapping to.
+/// Empty where the platform doesn't tell: on macOS and under Wayland.
+pub fn visible_windows() -> Vec<Rect> {
+    #[cfg(target_os = "windows")]
+    {
+        use windows_sys::Win32::Foundation::{BOOL, HWND, LPARAM, RECT};
+        use windows_sys::Win32::Graphics::Dwm::{
+            DwmGetWindowAttribute, DWMWA_CLOAKED, DWMWA_EXTENDED_FRAME_BOUNDS,
+        };
+        use windows_sys::Win32::UI::WindowsAndMessaging::{EnumWindows, IsIconic, IsWindowVisible};
+
+        // SAFETY: `lparam` is the `Vec` passed to EnumWindows below, which outlives the
+        // enumeration, and each out-parameter is sized to what the attribute writes
+        unsafe extern "system" fn collect(hwnd: HWND, lparam: LPARAM) -> BOOL {
+            let rects = &mut *(lparam as *mut Vec<Rect>);
+            if IsWindowVisible(hwnd) == 0 || IsIconic(hwnd) != 0 {
+                return 1;
+            }
+            // Suspended store apps and windows on other virtual desktops are "visible"
+            // but cloaked
+            let mut cloaked = 0u32;
+            DwmGetWindowAttribute(
+                hwnd,
+                DWMWA_CLOAKED,
+                &mut cloaked as *mut u32 as *mut _,
+                std::mem::size_of::<u32>() as u32,
+            );
+            if cloaked != 0 {
+                return 1;
+            }
+            // The frame bounds leave out the invisible resize border GetWindowRect includes
+            let mut r: RECT = std::mem::zeroed();
+            let hr = DwmGetWindowAttribute(
+                hwnd,
+                DWMWA_EXTENDED_FRAME_BOUNDS,
+                &mut r as *mut RECT as *mut _,
+                std::mem::size_of::<RECT>() as u32,
+            );
+            if hr == 0 && r.right > r.left && r.bottom > r.top {
+                rects.push(Rect {
+                    x: r.left,
+                    y: r.top,
+                    width: (r.right - r.left) as u32,
+                    height: (r.bottom - r.top) as u32,
+                });
+            }
+            (rects.len() < MAX_WINDOWS) as BOOL
+        }
+
+        let mut rects: Vec<Rect> = Vec::new();
+        unsafe {
+            EnumWindows(Some(collect), &mut rects as *mut Vec<Rect> as LPARAM);
+        }
+        rects
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        // XWayland only knows about X11 apps, and in its own coordinates
+        if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+            return Vec::new();
+        }
+        x11_windows()
+    }
+
+    #[cfg(not(any(target_os = "windows", target_os = "linux")))]
+    {
+        Vec::new()
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn x11_windows() -> Vec<Rect> {
+    use std::process::{Command, Stdio};
+
+    // _NET_CLIENT_LIST_STACKING(WINDOW): window id # 0x1e00003, 0x3a00007
+    let Some(list) =
+        crate::active_app::command_output("xprop", &["-root", "_NET_CLIENT_LIST_STACKING"])
+    else {
+        return Vec::new();
+    };
+    let ids: Vec<&str> = list
+        .split_once('#')
+        .map(|(_, ids)| {
+            ids.split(',')
+                .map(str::trim)
+                .filter(|id| !id.is_empty())
+                .collect()
+        })
+        .unwrap_or_default();
+
+    // The list runs bottom to top. Query the windows in parallel so the overlay doesn't
+    // wait for one xwininfo after another.
+    let queries: Vec<_> = ids
+        .iter()
+        .rev()
+        .take(MAX_WINDOWS)
+        .filter_map(|id| {
+            Command::new("xwininfo")
+                .args(["-id", id])
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn()
+                .ok()
+        })
+        .collect();
+    queries
+        .into_iter()
+        .filter_map(|query| query.wait_with_output().ok())
+        .filter(|output| output.status.success())
+        .filter_map(|output| parse_xwininfo(&String::from_utf8_lossy(&output.stdout)))
+        .collect()
+}
+
+/// Position and size of a mapped window from `xwininfo -id` output.
+#[cfg(target_os = "linux")]
+fn parse_xwininfo(info: &str) -> Option<Rect> {
+    let field = |name: &str| {
+        info.lines()
+            .filter_map(|line| line.trim().split_once(':'))
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| value.trim())
+    };
+    if field("Map State")? != "IsViewable" {
+        return None;
+    }
+    Some(Rect {
+        x: field("Absolute upper-left X")?.parse().ok()?,
+        y: field("Absolute upper-left Y")?.parse().ok()?,
+        width: field("Width")?.parse().ok()?,
+        height: field("Height")?.parse().ok()?,
+    })
+    .filter(|r| r.width > 0 && r.height > 0)
+}